@@ -0,0 +1,79 @@
+// Writes the bundled ROM database: the entries of a chip-8-database checkout
+// for the ROMs in `roms/`, and the checkout's revision in `UPSTREAM`.
+//
+//     cargo run --example database_subset -- ../../chip-8-database
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use core::cli::exit_with;
+use core::rom_hash;
+use serde_json::Value;
+
+fn read_json(path: &Path) -> Value {
+    let text = std::fs::read_to_string(path).unwrap_or_else(|e| exit_with(format!("Error reading {}: {e}", path.display())));
+    serde_json::from_str(&text).unwrap_or_else(|e| exit_with(format!("{}: {e}", path.display())))
+}
+
+fn write(path: &Path, contents: String) {
+    std::fs::write(path, contents).unwrap_or_else(|e| exit_with(format!("Error writing {}: {e}", path.display())));
+}
+
+fn write_json(path: &Path, value: &Value) {
+    write(path, serde_json::to_string_pretty(value).unwrap() + "\n");
+}
+
+fn main() {
+    let checkout = PathBuf::from(
+        std::env::args().nth(1).unwrap_or_else(|| exit_with("usage: database_subset CHIP-8-DATABASE-CHECKOUT")),
+    );
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let (upstream, bundled) = (checkout.join("database"), manifest.join("database"));
+
+    let mut hashes = Vec::new();
+    let roms = std::fs::read_dir(manifest.join("../roms")).unwrap_or_else(|e| exit_with(format!("Error reading roms: {e}")));
+    for entry in roms.flatten() {
+        if let Ok(rom) = std::fs::read(entry.path()) {
+            hashes.push(rom_hash(&rom));
+        }
+    }
+
+    // Keeps upstream's order and fields, minus the ROMs we don't ship
+    let Value::Array(all_programs) = read_json(&upstream.join("programs.json")) else {
+        exit_with("programs.json isn't a list")
+    };
+    let mut programs = Vec::new();
+    let mut program_of = BTreeMap::new();
+    for mut program in all_programs {
+        let Some(Value::Object(roms)) = program.get_mut("roms") else { continue };
+        roms.retain(|hash, _| hashes.contains(&hash.to_ascii_lowercase()));
+        if roms.is_empty() {
+            continue;
+        }
+        for hash in roms.keys() {
+            program_of.insert(hash.to_ascii_lowercase(), Value::from(programs.len()));
+        }
+        programs.push(program);
+    }
+    for hash in hashes.iter().filter(|hash| !program_of.contains_key(*hash)) {
+        eprintln!("{hash} isn't in the database");
+    }
+
+    let revision = Command::new("git")
+        .arg("-C")
+        .arg(&checkout)
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_else(|| exit_with(format!("{} isn't a git checkout", checkout.display())));
+
+    let count = programs.len();
+    write_json(&bundled.join("programs.json"), &Value::Array(programs));
+    write_json(&bundled.join("sha1-hashes.json"), &Value::Object(program_of.into_iter().collect()));
+    write_json(&bundled.join("platforms.json"), &read_json(&upstream.join("platforms.json")));
+    write(&bundled.join("UPSTREAM"), format!("https://github.com/chip-8/chip-8-database {revision}\n"));
+    println!("{count} programs from chip-8-database {revision}");
+}
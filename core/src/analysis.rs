@@ -0,0 +1,380 @@
+// Guesses which interpreter a ROM was written for by reading its code. The
+// analysis follows jumps, calls and skips from the start address, so data
+// between routines isn't mistaken for instructions, and keeps track of I
+// where it can to see what FX55 and FX33 overwrite.
+
+use crate::instructions::Decoded;
+use crate::{Quirks, RAM_SIZE, START_ADDR};
+
+/// The interpreters a ROM can be written for, from the oldest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    /// Nothing specific to one interpreter was found.
+    Chip8,
+    /// The original interpreter on the COSMAC VIP: the ROM calls machine
+    /// code, modifies itself or depends on how the VIP shifts and moves I.
+    CosmacVip,
+    /// CHIP-48 and SUPER-CHIP without their new instructions: shifts are
+    /// written for shifting VX in place and I doesn't move.
+    Chip48,
+    /// SUPER-CHIP: scrolling, high resolution, 16x16 sprites...
+    SuperChip,
+    /// XO-CHIP: SUPER-CHIP plus colour planes, audio patterns and 16-bit
+    /// addresses.
+    XoChip,
+}
+
+impl Platform {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Platform::Chip8 => "CHIP-8",
+            Platform::CosmacVip => "COSMAC VIP",
+            Platform::Chip48 => "CHIP-48",
+            Platform::SuperChip => "SUPER-CHIP",
+            Platform::XoChip => "XO-CHIP",
+        }
+    }
+
+    /// The id of the platform in the community CHIP-8 database (see
+    /// `RomDatabase`).
+    pub fn database_id(&self) -> &'static str {
+        match self {
+            Platform::Chip8 => "modernChip8",
+            Platform::CosmacVip => "originalChip8",
+            Platform::Chip48 => "chip48",
+            Platform::SuperChip => "superchip",
+            Platform::XoChip => "xochip",
+        }
+    }
+
+    /// Whether this emulator runs the platform's instructions.
+    pub fn is_supported(&self) -> bool {
+        matches!(self, Platform::Chip8 | Platform::CosmacVip | Platform::Chip48)
+    }
+}
+
+/// How often the analysis saw something and where it saw it first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Finding {
+    pub count: usize,
+    pub first: Option<u16>,
+}
+
+impl Finding {
+    fn add(&mut self, addr: u16) {
+        self.count += 1;
+        self.first.get_or_insert(addr);
+    }
+
+    pub fn found(&self) -> bool {
+        self.count > 0
+    }
+}
+
+/// What `analyze_rom` found in a ROM. Counts are of instructions, each
+/// counted once however many paths reach it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RomAnalysis {
+    /// Bytes of the ROM reached as instructions.
+    pub code_bytes: usize,
+    /// Opcodes no interpreter knows, where that path of the analysis stops.
+    pub unknown: Finding,
+    /// SUPER-CHIP instructions (also in XO-CHIP).
+    pub schip: Finding,
+    /// Instructions only XO-CHIP has.
+    pub xochip: Finding,
+    /// 0NNN, calls to COSMAC VIP machine code.
+    pub machine_code: Finding,
+    /// Instructions that FX55 or FX33 overwrite.
+    pub self_modifying: Finding,
+    /// 8XY6 and 8XYE with X and Y different and Y not 0, written to shift
+    /// VY as the VIP does.
+    pub shifts_vy: Finding,
+    /// 8X06 and 8X0E, the shifts of VX CHIP-48 assemblers wrote, which
+    /// shift V0 on the VIP.
+    pub shifts_in_place: Finding,
+    /// Instructions using I right after FX55 or FX65 moved it, as loops
+    /// storing or loading a block a few registers at a time do.
+    pub memory_loops: Finding,
+    /// FX55 writing back what FX65 just loaded, which only hits the same
+    /// bytes when I doesn't move.
+    pub load_then_store: Finding,
+    /// BNNN with a non-zero X, a different jump under SUPER-CHIP.
+    pub jumps_vx: Finding,
+}
+
+impl RomAnalysis {
+    /// The newest platform the ROM uses instructions of. Plain CHIP-8 ROMs
+    /// go to the VIP if they do what only works there, else to whichever of
+    /// the VIP and CHIP-48 their shifts and FX55/FX65 look written for.
+    pub fn platform(&self) -> Platform {
+        let vip = self.shifts_vy.count + self.memory_loops.count;
+        let chip48 = self.shifts_in_place.count + self.load_then_store.count;
+        if self.xochip.found() {
+            Platform::XoChip
+        } else if self.schip.found() {
+            Platform::SuperChip
+        } else if self.machine_code.found() || self.self_modifying.found() {
+            Platform::CosmacVip
+        } else if chip48 > vip {
+            Platform::Chip48
+        } else if vip > 0 {
+            Platform::CosmacVip
+        } else {
+            Platform::Chip8
+        }
+    }
+
+    /// Quirks to run the ROM with, those of its platform. ROMs that don't
+    /// depend on any get this emulator's defaults.
+    pub fn quirks(&self) -> Quirks {
+        match self.platform() {
+            Platform::Chip8 => Quirks::default(),
+            Platform::CosmacVip => Quirks::COSMAC_VIP,
+            Platform::Chip48 | Platform::SuperChip => Quirks::SCHIP,
+            Platform::XoChip => Quirks {
+                vf_reset: false,
+                memory_increment: true,
+                memory_increment_by_x: false,
+                shift_uses_vy: true,
+                jump_uses_vx: false,
+                clip_sprites: false,
+            },
+        }
+    }
+}
+
+// What's known about I on a path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IState {
+    Unknown,
+    At(u16),
+    // FX65 or FX55 ran since I was set, so where it points depends on a
+    // quirk
+    AfterLoad,
+    AfterStore,
+}
+
+impl IState {
+    // Paths reaching an address are followed once per kind of state
+    fn bit(self) -> u8 {
+        match self {
+            IState::Unknown => 1,
+            IState::At(_) => 2,
+            IState::AfterLoad => 4,
+            IState::AfterStore => 8,
+        }
+    }
+}
+
+// What the analysis saw at an address, as bits
+const INSTRUCTION: u16 = 1 << 0;
+const UNKNOWN: u16 = 1 << 1;
+const SCHIP: u16 = 1 << 2;
+const XOCHIP: u16 = 1 << 3;
+const MACHINE_CODE: u16 = 1 << 4;
+const SHIFTS_VY: u16 = 1 << 5;
+const SHIFTS_IN_PLACE: u16 = 1 << 6;
+const MEMORY_LOOPS: u16 = 1 << 7;
+const LOAD_THEN_STORE: u16 = 1 << 8;
+const JUMPS_VX: u16 = 1 << 9;
+
+// Where execution goes after an instruction
+enum Flow {
+    Next,
+    Stop,
+    Jump(u16),
+    Call(u16),
+    Skip,
+    // over the second half of F000 NNNN
+    Long,
+}
+
+// Extensions to the instruction set the decoder doesn't know
+enum Extension {
+    Schip,
+    XoChip,
+    // F000 NNNN, the one 4-byte instruction
+    LongI(u16),
+}
+
+fn extension(opcode: u16, next: u16) -> Option<Extension> {
+    let x = (opcode >> 8) & 0xF;
+    match (opcode >> 12, opcode & 0xFF) {
+        (0, 0xFB..=0xFF) if x == 0 => Some(Extension::Schip), // scroll, exit, resolution
+        (0, 0xC0..=0xCF) if x == 0 => Some(Extension::Schip), // scroll down
+        (0, 0xD0..=0xDF) if x == 0 => Some(Extension::XoChip), // scroll up
+        (0xD, _) if opcode & 0xF == 0 => Some(Extension::Schip), // 16x16 sprite
+        (0xF, 0x30 | 0x75 | 0x85) => Some(Extension::Schip), // big font, flags
+        (5, _) if matches!(opcode & 0xF, 2 | 3) => Some(Extension::XoChip), // save, load range
+        (0xF, 0x00) if x == 0 => Some(Extension::LongI(next)),
+        (0xF, 0x01 | 0x3A) => Some(Extension::XoChip), // plane, pitch
+        (0xF, 0x02) if x == 0 => Some(Extension::XoChip), // audio pattern
+        _ => None,
+    }
+}
+
+/// Reads the code of `rom` and reports what it uses. See
+/// `RomAnalysis::platform` and `quirks` for the suggestions.
+pub fn analyze_rom(rom: &[u8]) -> RomAnalysis {
+    let start = START_ADDR as usize;
+    let end = (start + rom.len()).min(RAM_SIZE);
+    let mut ram = [0; RAM_SIZE];
+    ram[start..end].copy_from_slice(&rom[..end - start]);
+    let opcode_at = |addr: usize| if addr + 1 < end { Some(u16::from_be_bytes([ram[addr], ram[addr + 1]])) } else { None };
+
+    let mut seen = [0u8; RAM_SIZE]; // the kinds of I state each address was reached with
+    let mut marks = [0u16; RAM_SIZE];
+    let mut code = [false; RAM_SIZE];
+    let mut written = [false; RAM_SIZE];
+    let mut queue = vec![(START_ADDR, IState::Unknown)];
+
+    while let Some((pc, i)) = queue.pop() {
+        let addr = pc as usize;
+        let Some(opcode) = opcode_at(addr).filter(|_| addr >= start && seen[addr] & i.bit() == 0) else {
+            continue;
+        };
+        seen[addr] |= i.bit();
+        marks[addr] |= INSTRUCTION;
+        let mut note = |mark: u16| marks[addr] |= mark;
+        let x = ((opcode >> 8) & 0xF) as u8;
+        let mut write = |from: u16, len: u16| {
+            for byte in from..from.saturating_add(len) {
+                if let Some(written) = written.get_mut(byte as usize) {
+                    *written = true;
+                }
+            }
+        };
+
+        code[addr] = true;
+        code[addr + 1] = true;
+        let mut next_i = i;
+
+        let flow = match Decoded::from_opcode(opcode) {
+            // DXY0 decodes, but draws nothing on a plain CHIP-8
+            Some(decoded) if !matches!(decoded, Decoded::Draw(_, _, 0)) => match decoded {
+                Decoded::RET => Flow::Stop,
+                Decoded::Jump(target) => Flow::Jump(target),
+                Decoded::Call(target) => Flow::Call(target),
+                Decoded::JumpOffset(target) => {
+                    if x != 0 {
+                        note(JUMPS_VX);
+                    }
+                    // the table usually starts at NNN
+                    Flow::Jump(target)
+                },
+                Decoded::SkipEq(..) | Decoded::SkipNeq(..) | Decoded::SkipEqReg(..) | Decoded::SkipNeqReg(..)
+                | Decoded::SkipKey(_) | Decoded::SkipNKey(_) => Flow::Skip,
+                Decoded::RightShift(x, y) | Decoded::LeftShift(x, y) if x != y => {
+                    note(if y == 0 { SHIFTS_IN_PLACE } else { SHIFTS_VY });
+                    Flow::Next
+                },
+                Decoded::SetIReg(addr) => {
+                    next_i = IState::At(addr);
+                    Flow::Next
+                },
+                Decoded::AddIReg(_) => {
+                    if matches!(i, IState::AfterLoad | IState::AfterStore) {
+                        note(MEMORY_LOOPS);
+                    }
+                    next_i = IState::Unknown;
+                    Flow::Next
+                },
+                Decoded::SetIRegFont(_) => {
+                    next_i = IState::Unknown;
+                    Flow::Next
+                },
+                Decoded::Draw(..) | Decoded::StoreBCD(_) | Decoded::StoreRegsToMem(_) | Decoded::LoadMemToRegs(_) => {
+                    match (decoded, i) {
+                        (Decoded::StoreRegsToMem(_), IState::AfterLoad) => note(LOAD_THEN_STORE),
+                        (_, IState::AfterLoad | IState::AfterStore) => note(MEMORY_LOOPS),
+                        _ => (),
+                    }
+                    match (decoded, i) {
+                        (Decoded::StoreBCD(_), IState::At(addr)) => write(addr, 3),
+                        (Decoded::StoreRegsToMem(x), IState::At(addr)) => write(addr, x as u16 + 1),
+                        _ => (),
+                    }
+                    match decoded {
+                        Decoded::LoadMemToRegs(_) => next_i = IState::AfterLoad,
+                        Decoded::StoreRegsToMem(_) => next_i = IState::AfterStore,
+                        _ => (),
+                    }
+                    Flow::Next
+                },
+                _ => Flow::Next,
+            },
+            // 0NNN apart from 00E0 and 00EE, unless it's a SUPER-CHIP one
+            _ if opcode >> 12 == 0 && extension(opcode, 0).is_none() => {
+                note(MACHINE_CODE);
+                Flow::Next
+            },
+            _ => match extension(opcode, opcode_at(addr + 2).unwrap_or(0)) {
+                Some(Extension::Schip) => {
+                    note(SCHIP);
+                    if opcode >> 12 == 0xF && opcode & 0xFF == 0x30 {
+                        next_i = IState::Unknown;
+                    }
+                    if opcode == 0x00FD { Flow::Stop } else { Flow::Next } // exit
+                },
+                Some(Extension::XoChip) => {
+                    note(XOCHIP);
+                    // 5XY2 stores VX to VY
+                    if opcode >> 12 == 5 && opcode & 0xF == 2 && let IState::At(addr) = i {
+                        let y = ((opcode >> 4) & 0xF) as u8;
+                        write(addr, x.abs_diff(y) as u16 + 1);
+                    }
+                    Flow::Next
+                },
+                Some(Extension::LongI(target)) => {
+                    note(XOCHIP);
+                    next_i = IState::At(target);
+                    Flow::Long
+                },
+                None => {
+                    note(UNKNOWN);
+                    Flow::Stop
+                },
+            },
+        };
+
+        // calls come back with whatever I the routine left
+        let successors = match flow {
+            Flow::Next => [Some((pc + 2, next_i)), None, None],
+            Flow::Stop => [None; 3],
+            Flow::Jump(target) => [Some((target, next_i)), None, None],
+            Flow::Call(target) => [Some((target, next_i)), Some((pc + 2, IState::Unknown)), None],
+            // XO-CHIP skips all of F000 NNNN
+            Flow::Skip => [Some((pc + 2, i)), Some((pc + 4, i)), (opcode_at(addr + 2) == Some(0xF000)).then_some((pc + 6, i))],
+            Flow::Long => {
+                code[(addr + 2).min(RAM_SIZE - 1)..(addr + 4).min(RAM_SIZE)].fill(true);
+                [Some((pc + 4, next_i)), None, None]
+            },
+        };
+        queue.extend(successors.into_iter().flatten());
+    }
+
+    let mut analysis = RomAnalysis { code_bytes: code.iter().filter(|&&byte| byte).count(), ..RomAnalysis::default() };
+    for addr in start..end - 1 {
+        let findings = [
+            (UNKNOWN, &mut analysis.unknown),
+            (SCHIP, &mut analysis.schip),
+            (XOCHIP, &mut analysis.xochip),
+            (MACHINE_CODE, &mut analysis.machine_code),
+            (SHIFTS_VY, &mut analysis.shifts_vy),
+            (SHIFTS_IN_PLACE, &mut analysis.shifts_in_place),
+            (MEMORY_LOOPS, &mut analysis.memory_loops),
+            (LOAD_THEN_STORE, &mut analysis.load_then_store),
+            (JUMPS_VX, &mut analysis.jumps_vx),
+        ];
+        for (mark, finding) in findings {
+            if marks[addr] & mark != 0 {
+                finding.add(addr as u16);
+            }
+        }
+        if marks[addr] & INSTRUCTION != 0 && (written[addr] || written[addr + 1]) {
+            analysis.self_modifying.add(addr as u16);
+        }
+    }
+    analysis
+}
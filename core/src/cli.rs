@@ -0,0 +1,29 @@
+// Command line helpers shared by the frontends (gui, headless, tui), which
+// all parse their flags by hand and give up on the first bad one.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::Quirks;
+
+/// Prints `message` to stderr and exits with status 1.
+pub fn exit_with(message: impl fmt::Display) -> ! {
+    eprintln!("{message}");
+    std::process::exit(1);
+}
+
+/// Parses the argument after `flag`, or exits saying it `expected` something
+/// else.
+pub fn flag_value<T: FromStr>(argv: &mut impl Iterator<Item = String>, flag: &str, expected: &str) -> T {
+    match argv.next().map(|v| v.parse::<T>()) {
+        Some(Ok(value)) => value,
+        _ => exit_with(format!("{flag} expects {expected}")),
+    }
+}
+
+/// The quirk profile called `name`, or exits listing the valid ones.
+pub fn quirks_or_exit(name: &str) -> Quirks {
+    Quirks::from_name(name).unwrap_or_else(|| {
+        exit_with(format!("unknown quirk profile '{name}', expected one of: {}", Quirks::PROFILES.join(", ")))
+    })
+}
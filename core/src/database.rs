@@ -0,0 +1,261 @@
+// ROM metadata by SHA-1, in the format of the community CHIP-8 database
+// (github.com/chip-8/chip-8-database): `programs.json` lists programs and
+// their ROMs, `sha1-hashes.json` maps hashes to program indices and
+// `platforms.json` has each platform's quirks and speed. A small database
+// covering `roms/` is bundled; a checkout of the full one can be opened
+// instead.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+use crate::{find_game, rom_hash, Buzzer, Display, Emu, Keypad, Quirks, Rng};
+
+const PROGRAMS_FILE: &str = "programs.json";
+const HASHES_FILE: &str = "sha1-hashes.json";
+const PLATFORMS_FILE: &str = "platforms.json";
+
+/// What the database knows about a ROM.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RomInfo {
+    pub title: String,
+    pub authors: Vec<String>,
+    pub release: Option<String>,
+    pub description: Option<String>,
+    /// Platforms the ROM runs on, by database id (`originalChip8`, `chip48`,
+    /// `superchip`, `xochip`...). The first is the one it was written for.
+    pub platforms: Vec<String>,
+    /// The first platform's quirks with the ROM's own changes, if the
+    /// platform is known.
+    pub quirks: Option<Quirks>,
+    /// Instructions per frame: the ROM's tick rate, or its platform's.
+    pub ipf: Option<usize>,
+    pub colors: Option<RomColors>,
+    pub keys: KeyHints,
+}
+
+/// The ROM's own palette, as RGB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RomColors {
+    pub background: [u8; 3],
+    pub foreground: [u8; 3],
+}
+
+/// The CHIP-8 keys a ROM uses for each direction and action, to put them
+/// on the arrow keys or a D-pad.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyHints {
+    pub up: Option<u8>,
+    pub down: Option<u8>,
+    pub left: Option<u8>,
+    pub right: Option<u8>,
+    pub a: Option<u8>,
+    pub b: Option<u8>,
+    pub player2_up: Option<u8>,
+    pub player2_down: Option<u8>,
+    pub player2_left: Option<u8>,
+    pub player2_right: Option<u8>,
+    pub player2_a: Option<u8>,
+    pub player2_b: Option<u8>,
+}
+
+/// A ROM database, see `bundled` and `open`.
+pub struct RomDatabase {
+    programs: Vec<Program>,
+    hashes: HashMap<String, usize>,
+    platforms: HashMap<String, Platform>,
+}
+
+impl RomDatabase {
+    /// The database shipped with the emulator.
+    pub fn bundled() -> &'static RomDatabase {
+        static BUNDLED: OnceLock<RomDatabase> = OnceLock::new();
+        BUNDLED.get_or_init(|| {
+            RomDatabase::from_json(
+                include_str!("../database/programs.json"),
+                include_str!("../database/sha1-hashes.json"),
+                include_str!("../database/platforms.json"),
+            )
+            .expect("bundled ROM database")
+        })
+    }
+
+    /// Reads the three files from a directory, such as the `database`
+    /// directory of a chip-8-database checkout.
+    pub fn open(dir: &Path) -> io::Result<RomDatabase> {
+        let read = |file| {
+            let path = dir.join(file);
+            std::fs::read_to_string(&path).map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))
+        };
+        RomDatabase::from_json(&read(PROGRAMS_FILE)?, &read(HASHES_FILE)?, &read(PLATFORMS_FILE)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {e}", dir.display())))
+    }
+
+    pub fn from_json(programs: &str, hashes: &str, platforms: &str) -> serde_json::Result<RomDatabase> {
+        let programs: Vec<Program> = serde_json::from_str(programs)?;
+        let hashes: HashMap<String, usize> = serde_json::from_str(hashes)?;
+        let platforms: Vec<Platform> = serde_json::from_str(platforms)?;
+        if let Some((hash, _)) = hashes.iter().find(|&(_, &index)| index >= programs.len()) {
+            return Err(serde::de::Error::custom(format!("{hash} points past the last program")));
+        }
+
+        Ok(RomDatabase {
+            programs,
+            hashes: hashes.into_iter().map(|(hash, index)| (hash.to_ascii_lowercase(), index)).collect(),
+            platforms: platforms.into_iter().map(|platform| (platform.id.clone(), platform)).collect(),
+        })
+    }
+
+    /// Looks up a ROM by its SHA-1, as `rom_hash` returns it.
+    pub fn lookup(&self, sha1: &str) -> Option<RomInfo> {
+        let sha1 = sha1.to_ascii_lowercase();
+        let program = &self.programs[*self.hashes.get(&sha1)?];
+        let rom = program.roms.iter().find(|(hash, _)| hash.eq_ignore_ascii_case(&sha1)).map(|(_, rom)| rom);
+
+        let mut info = RomInfo {
+            title: program.title.clone(),
+            authors: program.authors.clone(),
+            release: program.release.clone(),
+            description: program.description.clone(),
+            platforms: Vec::new(),
+            quirks: None,
+            ipf: None,
+            colors: None,
+            keys: KeyHints::default(),
+        };
+        let Some(rom) = rom else {
+            return Some(info);
+        };
+
+        let platform = rom.platforms.first().and_then(|id| self.platforms.get(id));
+        info.platforms = rom.platforms.clone();
+        info.quirks = platform.map(|platform| {
+            let changes = rom.quirky_platforms.get(&platform.id).copied().unwrap_or_default();
+            platform.quirks.with(changes).to_quirks()
+        });
+        info.ipf = rom.tickrate.or(platform.and_then(|platform| platform.default_tickrate));
+        info.colors = rom.colors.as_ref().and_then(Colors::to_rom_colors);
+        info.keys = rom.keys;
+        Some(info)
+    }
+}
+
+impl<D: Display, K: Keypad, B: Buzzer, R: Rng> Emu<D, K, B, R> {
+    /// Loads `rom` and looks it up in `database`. Known ROMs get their
+    /// quirks, and the rules of games in the bundled list (see `find_game`)
+    /// are set. The rest is up to the frontend: IPF, colours and keys are in
+    /// the returned info.
+    pub fn load_rom_with_info(&mut self, rom: &[u8], database: &RomDatabase) -> Option<RomInfo> {
+        self.load_rom(rom);
+        let hash = rom_hash(rom);
+        self.set_game_rules(find_game(&hash).map(|game| game.rules));
+
+        let info = database.lookup(&hash)?;
+        if let Some(quirks) = info.quirks {
+            self.set_quirks(quirks);
+        }
+        Some(info)
+    }
+}
+
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    release: Option<String>,
+    description: Option<String>,
+    #[serde(default)]
+    roms: HashMap<String, Rom>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Rom {
+    #[serde(default)]
+    platforms: Vec<String>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, QuirkFlags>,
+    tickrate: Option<usize>,
+    colors: Option<Colors>,
+    #[serde(default)]
+    keys: KeyHints,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Platform {
+    id: String,
+    #[serde(default)]
+    quirks: QuirkFlags,
+    default_tickrate: Option<usize>,
+}
+
+// The database's quirks, named after what differs from the original
+// interpreter. `vblank` (drawing waits for the display interrupt) is what
+// `Timing::CosmacVip` does and isn't a quirk here.
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+struct QuirkFlags {
+    shift: Option<bool>,
+    memory_increment_by_x: Option<bool>,
+    memory_leave_i_unchanged: Option<bool>,
+    wrap: Option<bool>,
+    jump: Option<bool>,
+    logic: Option<bool>,
+}
+
+impl QuirkFlags {
+    // `changes` wins where it says anything
+    fn with(self, changes: QuirkFlags) -> QuirkFlags {
+        QuirkFlags {
+            shift: changes.shift.or(self.shift),
+            memory_increment_by_x: changes.memory_increment_by_x.or(self.memory_increment_by_x),
+            memory_leave_i_unchanged: changes.memory_leave_i_unchanged.or(self.memory_leave_i_unchanged),
+            wrap: changes.wrap.or(self.wrap),
+            jump: changes.jump.or(self.jump),
+            logic: changes.logic.or(self.logic),
+        }
+    }
+
+    fn to_quirks(self) -> Quirks {
+        let on = |flag: Option<bool>| flag.unwrap_or(false);
+        Quirks {
+            vf_reset: on(self.logic),
+            memory_increment: !on(self.memory_leave_i_unchanged),
+            memory_increment_by_x: on(self.memory_increment_by_x),
+            shift_uses_vy: !on(self.shift),
+            jump_uses_vx: on(self.jump),
+            clip_sprites: !on(self.wrap),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Colors {
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+impl Colors {
+    // XO-CHIP ROMs list 4 colours, the first two are the CHIP-8 ones
+    fn to_rom_colors(&self) -> Option<RomColors> {
+        match self.pixels.as_slice() {
+            [background, foreground, ..] => Some(RomColors { background: parse_rgb(background)?, foreground: parse_rgb(foreground)? }),
+            _ => None,
+        }
+    }
+}
+
+fn parse_rgb(color: &str) -> Option<[u8; 3]> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    Some([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+}
@@ -0,0 +1,120 @@
+use crate::{Display, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// One bit per pixel, one u64 per line, so the width is fixed at 64
+const _: () = assert!(SCREEN_WIDTH == u64::BITS as usize);
+const _: () = assert!(SCREEN_HEIGHT <= u32::BITS as usize);
+
+/// Region of the screen that changed, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirtyRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// The monochrome display, stored as packed rows. The most significant bit of
+/// a row is the leftmost pixel (x = 0), the same order as sprite bytes.
+#[derive(Clone)]
+pub struct Framebuffer {
+    rows: [u64; SCREEN_HEIGHT],
+    dirty_rows: u32, // bit y set = row y changed
+    dirty_cols: u64, // same layout as a row, union of all changed pixels
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Framebuffer {
+    pub fn new() -> Self {
+        let mut fb = Self { rows: [0; SCREEN_HEIGHT], dirty_rows: 0, dirty_cols: 0 };
+        fb.mark_all_dirty();
+        fb
+    }
+
+    /// A screen showing `rows`, all of it dirty.
+    pub fn from_rows(rows: [u64; SCREEN_HEIGHT]) -> Self {
+        let mut fb = Self { rows, dirty_rows: 0, dirty_cols: 0 };
+        fb.mark_all_dirty();
+        fb
+    }
+
+    pub fn rows(&self) -> &[u64] {
+        &self.rows
+    }
+
+    /// Writes the screen to `out` as one byte per pixel, 0 or 1, row by row
+    /// from the top left. `out` must hold `SCREEN_WIDTH * SCREEN_HEIGHT`
+    /// bytes; any after those are left alone.
+    pub fn write_bytes(&self, out: &mut [u8]) {
+        assert!(out.len() >= SCREEN_WIDTH * SCREEN_HEIGHT, "buffer too small for the screen");
+        for (row, line) in self.rows.iter().zip(out.chunks_exact_mut(SCREEN_WIDTH)) {
+            for (x, pixel) in line.iter_mut().enumerate() {
+                *pixel = (row >> (SCREEN_WIDTH - 1 - x)) as u8 & 1;
+            }
+        }
+    }
+
+    /// Returns the region that changed since the last call, if any, and
+    /// starts tracking again from a clean state.
+    pub fn take_dirty(&mut self) -> Option<DirtyRect> {
+        if self.dirty_rows == 0 {
+            return None;
+        }
+
+        let y = self.dirty_rows.trailing_zeros() as usize;
+        let height = u32::BITS as usize - self.dirty_rows.leading_zeros() as usize - y;
+        let x = self.dirty_cols.leading_zeros() as usize;
+        let width = SCREEN_WIDTH - self.dirty_cols.trailing_zeros() as usize - x;
+
+        self.dirty_rows = 0;
+        self.dirty_cols = 0;
+        Some(DirtyRect { x, y, width, height })
+    }
+
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty_rows = u32::MAX >> (u32::BITS as usize - SCREEN_HEIGHT);
+        self.dirty_cols = u64::MAX;
+    }
+}
+
+impl Display for Framebuffer {
+    fn pixel(&self, x: usize, y: usize) -> bool {
+        (self.rows[y] >> (SCREEN_WIDTH - 1 - x)) & 1 != 0
+    }
+
+    fn clear(&mut self) {
+        for (y, row) in self.rows.iter_mut().enumerate() {
+            if *row != 0 {
+                self.dirty_rows |= 1 << y;
+                self.dirty_cols |= *row;
+                *row = 0;
+            }
+        }
+    }
+
+    fn draw_sprite_row(&mut self, x: usize, y: usize, sprite_row: u8, clip: bool) -> bool {
+        // place the sprite in the leftmost byte, then move it into position
+        let sprite = (sprite_row as u64) << (SCREEN_WIDTH - 8);
+        let x = (x % SCREEN_WIDTH) as u32;
+        let bits = if clip { sprite >> x } else { sprite.rotate_right(x) };
+        if bits == 0 {
+            return false;
+        }
+
+        let y = y % SCREEN_HEIGHT;
+        let collision = self.rows[y] & bits != 0;
+        self.rows[y] ^= bits;
+        self.dirty_rows |= 1 << y;
+        self.dirty_cols |= bits;
+        collision
+    }
+
+    // a fresh screen, so the frontend redraws all of it
+    fn reset(&mut self) {
+        *self = Framebuffer::new();
+    }
+}
@@ -0,0 +1,223 @@
+// A Gym-style environment for reinforcement learning: reset to a ROM, step a
+// few frames with some keys held, get the screen back and a reward read out of
+// the game's memory. `Env` is `Clone`, so an agent can fork environments or
+// run a batch of them on several threads.
+
+use std::sync::Arc;
+
+use crate::{HeadlessEmu, RamValue, MAX_ROM_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+const DEFAULT_FRAMES_PER_STEP: u32 = 4;
+const DEFAULT_IPF: usize = 10;
+
+/// The screen, one byte per pixel (0 or 1), row by row from the top left.
+pub type Observation = [u8; SCREEN_WIDTH * SCREEN_HEIGHT];
+
+/// Turns the machine's state after a step into a reward. Closures taking the
+/// emulator work too, for one-off experiments.
+pub trait Reward {
+    /// Called at the end of every step.
+    fn reward(&mut self, emu: &HeadlessEmu) -> f32;
+
+    /// True once the game is over.
+    fn done(&self, _emu: &HeadlessEmu) -> bool {
+        false
+    }
+
+    /// Called after the ROM is reloaded, before the first step.
+    fn reset(&mut self, _emu: &HeadlessEmu) {}
+}
+
+impl<F: FnMut(&HeadlessEmu) -> f32> Reward for F {
+    fn reward(&mut self, emu: &HeadlessEmu) -> f32 {
+        self(emu)
+    }
+}
+
+/// Rewards the points scored during the step, read from `score`.
+#[derive(Debug, Clone, Copy)]
+pub struct ScoreDelta {
+    score: RamValue,
+    last: u32,
+}
+
+impl ScoreDelta {
+    pub fn new(score: RamValue) -> Self {
+        Self { score, last: 0 }
+    }
+}
+
+impl Reward for ScoreDelta {
+    fn reward(&mut self, emu: &HeadlessEmu) -> f32 {
+        let score = self.score.read(emu.get_ram());
+        let reward = score as f32 - self.last as f32;
+        self.last = score;
+        reward
+    }
+
+    fn reset(&mut self, emu: &HeadlessEmu) {
+        self.last = self.score.read(emu.get_ram());
+    }
+}
+
+/// Rewards the points scored during the step and ends the episode with the
+/// game, according to the game rules set on the emulator (see `Env::for_game`).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GameScore {
+    last: u32,
+}
+
+impl GameScore {
+    fn score(emu: &HeadlessEmu) -> u32 {
+        emu.game_status().and_then(|status| status.score).unwrap_or(0)
+    }
+}
+
+impl Reward for GameScore {
+    fn reward(&mut self, emu: &HeadlessEmu) -> f32 {
+        let score = Self::score(emu);
+        let reward = score as f32 - self.last as f32;
+        self.last = score;
+        reward
+    }
+
+    fn done(&self, emu: &HeadlessEmu) -> bool {
+        emu.game_status().is_some_and(|status| status.is_finished())
+    }
+
+    fn reset(&mut self, emu: &HeadlessEmu) {
+        self.last = Self::score(emu);
+    }
+}
+
+/// What `Env::step` returns, named after Gymnasium's tuple.
+#[derive(Clone)]
+pub struct Step {
+    pub observation: Observation,
+    pub reward: f32,
+    /// The game is over, see `Reward::done`.
+    pub terminated: bool,
+    /// The episode hit the step limit set with `set_max_steps`.
+    pub truncated: bool,
+}
+
+/// One ROM running in a headless emulator. Cloning it copies the machine, so
+/// both copies carry on independently from the same state.
+#[derive(Clone)]
+pub struct Env<W = ScoreDelta> {
+    emu: HeadlessEmu,
+    rom: Arc<[u8]>, // shared between clones
+    reward: W,
+    frames_per_step: u32,
+    ipf: usize,
+    steps: u64,
+    max_steps: Option<u64>,
+}
+
+impl Env<GameScore> {
+    /// An environment for a game in the bundled list (see `find_game`),
+    /// rewarding its score. `None` for other ROMs.
+    pub fn for_game(rom: &[u8]) -> Option<Self> {
+        let game = crate::find_game(&crate::rom_hash(rom))?;
+        let mut env = Self::new(rom, GameScore::default());
+        env.emu.set_game_rules(Some(game.rules));
+        env.reset();
+        Some(env)
+    }
+}
+
+impl<W: Reward> Env<W> {
+    /// Loads `rom`, ready for the first step. Panics if it's larger than
+    /// `MAX_ROM_SIZE`.
+    pub fn new(rom: &[u8], reward: W) -> Self {
+        assert!(rom.len() <= MAX_ROM_SIZE, "ROM is larger than {MAX_ROM_SIZE} bytes");
+        let mut env = Self {
+            emu: HeadlessEmu::headless(),
+            rom: rom.into(),
+            reward,
+            frames_per_step: DEFAULT_FRAMES_PER_STEP,
+            ipf: DEFAULT_IPF,
+            steps: 0,
+            max_steps: None,
+        };
+        env.reset();
+        env
+    }
+
+    /// Restarts the ROM from scratch. Settings (quirks, timing, seed) are
+    /// kept, so with the same seed every episode plays out the same way.
+    pub fn reset(&mut self) -> Observation {
+        self.emu.reset();
+        self.emu.load_rom(&self.rom);
+        self.steps = 0;
+        self.reward.reset(&self.emu);
+        self.observation()
+    }
+
+    /// Reseeds CXNN, then restarts the ROM.
+    pub fn reset_with_seed(&mut self, seed: u64) -> Observation {
+        self.emu.set_seed(seed);
+        self.reset()
+    }
+
+    /// Runs `frames_per_step` frames with the keys in `action` held down,
+    /// bit k standing for key k, and everything else released.
+    pub fn step(&mut self, action: u16) -> Step {
+        for key in 0..16 {
+            self.emu.keypress(key, action & (1 << key) != 0);
+        }
+        for _ in 0..self.frames_per_step {
+            self.emu.run_frame(self.ipf);
+        }
+        self.steps += 1;
+
+        Step {
+            observation: self.observation(),
+            reward: self.reward.reward(&self.emu),
+            terminated: self.reward.done(&self.emu),
+            truncated: self.max_steps.is_some_and(|max| self.steps >= max),
+        }
+    }
+
+    pub fn observation(&self) -> Observation {
+        let mut observation = [0; SCREEN_WIDTH * SCREEN_HEIGHT];
+        self.emu.display().write_bytes(&mut observation);
+        observation
+    }
+
+    /// Steps taken since the last reset.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Frames emulated per step (4 by default): agents rarely need to act
+    /// 60 times a second.
+    pub fn set_frames_per_step(&mut self, frames: u32) {
+        self.frames_per_step = frames.max(1);
+    }
+
+    /// Instructions per frame with `Timing::Instructions` (10 by default).
+    pub fn set_ipf(&mut self, ipf: usize) {
+        self.ipf = ipf;
+    }
+
+    /// Ends episodes after `max_steps` steps, or never with `None` (the
+    /// default).
+    pub fn set_max_steps(&mut self, max_steps: Option<u64>) {
+        self.max_steps = max_steps;
+    }
+
+    /// The machine, e.g. to set quirks or timing, or read memory. Changes
+    /// other than settings are undone by the next `reset`.
+    pub fn emu(&self) -> &HeadlessEmu {
+        &self.emu
+    }
+
+    pub fn emu_mut(&mut self) -> &mut HeadlessEmu {
+        &mut self.emu
+    }
+
+    pub fn reward_fn(&self) -> &W {
+        &self.reward
+    }
+}
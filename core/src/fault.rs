@@ -0,0 +1,36 @@
+// Instructions the machine can't run. `Emu::try_tick` and
+// `Emu::try_run_frame` stop before them and report why, for frontends that
+// can't catch a panic (WebAssembly aborts on one).
+
+use core::fmt;
+
+/// Why the instruction at `pc` can't run. The machine is left as it was
+/// before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The program counter is past the last instruction in memory.
+    PcOutOfRange { pc: u16 },
+    /// Not a CHIP-8 instruction, e.g. a SUPER-CHIP one.
+    UnknownOpcode { pc: u16, opcode: u16 },
+    /// 00EE with nothing on the stack.
+    StackUnderflow { pc: u16 },
+    /// EX9E or EXA1 with VX above 0xF.
+    InvalidKey { pc: u16, key: u8 },
+    /// DXYN, FX33, FX55 or FX65 would go past the end of memory from I.
+    MemoryOutOfRange { pc: u16, i: u16 },
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Fault::PcOutOfRange { pc } => write!(f, "program counter {pc:#05X} is past the end of memory"),
+            Fault::UnknownOpcode { pc, opcode } => write!(f, "unknown opcode {opcode:04X} at {pc:#05X}"),
+            Fault::StackUnderflow { pc } => write!(f, "return with an empty stack at {pc:#05X}"),
+            Fault::InvalidKey { pc, key } => write!(f, "key {key:#04X} doesn't exist, at {pc:#05X}"),
+            Fault::MemoryOutOfRange { pc, i } => write!(f, "I = {i:#05X} goes past the end of memory at {pc:#05X}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Fault {}
@@ -0,0 +1,141 @@
+// What a particular game keeps in memory: its score, lives and whether it's
+// over. Games don't agree on any of it, so each one needs rules saying where
+// to look (`games.toml` has the known ones), which the emulator evaluates
+// at the end of every frame.
+
+use crate::{Buzzer, Display, Emu, Keypad, Rng};
+
+/// How a number is stored in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// An unsigned integer, most significant byte first.
+    Binary,
+    /// One decimal digit per byte, most significant first, as FX33 writes.
+    Bcd,
+}
+
+/// A number a game keeps in RAM, such as its score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RamValue {
+    pub addr: u16,
+    /// Number of bytes, at most 4 for `Binary` and 9 for `Bcd`.
+    pub len: u8,
+    pub encoding: Encoding,
+}
+
+impl RamValue {
+    pub fn new(addr: u16, len: u8, encoding: Encoding) -> Self {
+        Self { addr, len, encoding }
+    }
+
+    /// Decodes the value from `ram`. Bytes past the end of memory read as 0.
+    pub fn read(&self, ram: &[u8]) -> u32 {
+        let start = self.addr as usize;
+        let bytes = (start..start + self.len as usize).map(|addr| ram.get(addr).copied().unwrap_or(0));
+        match self.encoding {
+            Encoding::Binary => bytes.fold(0, |value, byte| value << 8 | byte as u32),
+            Encoding::Bcd => bytes.fold(0, |value, digit| value * 10 + (digit % 10) as u32),
+        }
+    }
+}
+
+/// Where a game keeps a number. Many only copy their score to RAM (with
+/// FX33) when they draw it, or never, so it can be read from a register too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameValue {
+    Ram(RamValue),
+    /// V0 to VF.
+    Register(u8),
+}
+
+impl GameValue {
+    pub fn read<D: Display, K: Keypad, B: Buzzer, R: Rng>(&self, emu: &Emu<D, K, B, R>) -> u32 {
+        match self {
+            GameValue::Ram(value) => value.read(&emu.ram),
+            GameValue::Register(x) => emu.v_reg[*x as usize & 0xF] as u32,
+        }
+    }
+}
+
+/// True while `value` equals `equals`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub value: GameValue,
+    pub equals: u32,
+}
+
+impl Condition {
+    pub fn holds<D: Display, K: Keypad, B: Buzzer, R: Rng>(&self, emu: &Emu<D, K, B, R>) -> bool {
+        self.value.read(emu) == self.equals
+    }
+}
+
+/// Where one game keeps its state. Everything is optional, not every game has
+/// lives or an ending.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GameRules {
+    pub score: Option<GameValue>,
+    pub lives: Option<GameValue>,
+    /// The player lost.
+    pub game_over: Option<Condition>,
+    /// The player finished the game.
+    pub won: Option<Condition>,
+}
+
+/// The game's state at the end of the last frame, according to its rules.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GameStatus {
+    pub score: Option<u32>,
+    pub lives: Option<u32>,
+    pub game_over: bool,
+    pub won: bool,
+}
+
+impl GameStatus {
+    /// The game ended either way.
+    pub fn is_finished(&self) -> bool {
+        self.game_over || self.won
+    }
+}
+
+impl GameRules {
+    /// The state right now. Unlike `Emu::game_status`, the conditions aren't
+    /// required to have been false first.
+    pub fn evaluate<D: Display, K: Keypad, B: Buzzer, R: Rng>(&self, emu: &Emu<D, K, B, R>) -> GameStatus {
+        GameStatus {
+            score: self.score.map(|score| score.read(emu)),
+            lives: self.lives.map(|lives| lives.read(emu)),
+            game_over: self.game_over.is_some_and(|condition| condition.holds(emu)),
+            won: self.won.is_some_and(|condition| condition.holds(emu)),
+        }
+    }
+}
+
+// The rules and the status they gave at the end of the last frame. Games set
+// up their variables after power-on, when registers and most of RAM are 0,
+// which would often read as a lost game. So conditions only count once they've
+// been false since the game (re)started.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct GameTracker {
+    pub rules: Option<GameRules>,
+    status: GameStatus,
+    armed: [bool; 2], // game over, won
+}
+
+impl GameTracker {
+    pub fn new(rules: Option<GameRules>) -> Self {
+        Self { rules, ..Self::default() }
+    }
+
+    pub fn status(&self) -> Option<GameStatus> {
+        self.rules.map(|_| self.status)
+    }
+
+    pub fn update(&mut self, mut status: GameStatus) {
+        for (armed, reached) in self.armed.iter_mut().zip([&mut status.game_over, &mut status.won]) {
+            *armed |= !*reached;
+            *reached &= *armed;
+        }
+        self.status = status;
+    }
+}
@@ -0,0 +1,131 @@
+// The list of known games and their rules, from `games.toml`. The format is
+// described at the top of that file.
+
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+use crate::{Condition, Encoding, GameRules, GameValue, RamValue, RAM_SIZE};
+
+const BUNDLED: &str = include_str!("../games.toml");
+
+/// A known game and where it keeps its state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameInfo {
+    pub title: String,
+    /// SHA-1 of the ROM, as `rom_hash` returns it.
+    pub sha1: String,
+    pub rules: GameRules,
+}
+
+/// Looks up a ROM by hash in the bundled list.
+pub fn find_game(sha1: &str) -> Option<&'static GameInfo> {
+    static GAMES: OnceLock<Vec<GameInfo>> = OnceLock::new();
+    GAMES
+        .get_or_init(|| parse_games(BUNDLED).expect("bundled games.toml"))
+        .iter()
+        .find(|game| game.sha1.eq_ignore_ascii_case(sha1))
+}
+
+/// Reads a list of games in the `games.toml` format, e.g. to add ROMs that
+/// aren't bundled.
+pub fn parse_games(text: &str) -> Result<Vec<GameInfo>, toml::de::Error> {
+    let file: File = toml::from_str(text)?;
+    Ok(file
+        .games
+        .into_iter()
+        .map(|entry| GameInfo {
+            title: entry.title,
+            sha1: entry.sha1.to_ascii_lowercase(),
+            rules: GameRules {
+                score: entry.score.map(|value| value.0),
+                lives: entry.lives.map(|value| value.0),
+                game_over: entry.game_over.map(Condition::from),
+                won: entry.won.map(Condition::from),
+            },
+        })
+        .collect())
+}
+
+#[derive(Deserialize)]
+struct File {
+    #[serde(default, rename = "game")]
+    games: Vec<Entry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    title: String,
+    sha1: String,
+    score: Option<Value>,
+    lives: Option<Value>,
+    game_over: Option<ConditionDef>,
+    won: Option<ConditionDef>,
+}
+
+#[derive(Deserialize)]
+struct ConditionDef {
+    #[serde(flatten)]
+    value: Value,
+    equals: u32,
+}
+
+impl From<ConditionDef> for Condition {
+    fn from(def: ConditionDef) -> Self {
+        Condition { value: def.value.0, equals: def.equals }
+    }
+}
+
+// Checked while parsing, so errors point at the value
+#[derive(Deserialize)]
+#[serde(try_from = "ValueDef")]
+struct Value(GameValue);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ValueDef {
+    Register { register: u8 },
+    Ram {
+        addr: u16,
+        #[serde(default = "one_byte")]
+        len: u8,
+        #[serde(default)]
+        encoding: EncodingDef,
+    },
+}
+
+fn one_byte() -> u8 {
+    1
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+enum EncodingDef {
+    #[default]
+    Binary,
+    Bcd,
+}
+
+impl TryFrom<ValueDef> for Value {
+    type Error = String;
+
+    fn try_from(def: ValueDef) -> Result<Self, String> {
+        match def {
+            ValueDef::Register { register } if register > 0xF => Err(format!("there's no register V{register:X}")),
+            ValueDef::Register { register } => Ok(Value(GameValue::Register(register))),
+            ValueDef::Ram { addr, len, encoding } => {
+                if len == 0 || addr as usize + len as usize > RAM_SIZE {
+                    return Err(format!("{len} bytes at {addr:#X} aren't in RAM"));
+                }
+                let encoding = match encoding {
+                    EncodingDef::Binary if len > 4 => return Err("binary values are at most 4 bytes".to_string()),
+                    EncodingDef::Binary => Encoding::Binary,
+                    EncodingDef::Bcd if len > 9 => return Err("BCD values are at most 9 digits".to_string()),
+                    EncodingDef::Bcd => Encoding::Bcd,
+                };
+                Ok(Value(GameValue::Ram(RamValue::new(addr, len, encoding))))
+            },
+        }
+    }
+}
@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum Decoded {
     // TODO
     NOP,                // 0x0000
@@ -17,9 +17,9 @@ pub enum Decoded {
     Xor(u8, u8),        // 0x8XY3
     AddRegReg(u8, u8),  // 0x8XY4
     SubRegReg(u8, u8),  // 0x8XY5
-    RightShift(u8),     // 0x8XY6
+    RightShift(u8, u8), // 0x8XY6
     SubRegRegRev(u8, u8), // 0x8XY7
-    LeftShift(u8),      // 0x8XYE
+    LeftShift(u8, u8),  // 0x8XYE
     SkipNeqReg(u8, u8), // 0x9XY0
     SetIReg(u16),       // 0xANNN
     JumpOffset(u16),    // 0xBNNN
@@ -37,3 +37,53 @@ pub enum Decoded {
     StoreRegsToMem(u8), // 0xFX55
     LoadMemToRegs(u8),  // 0xFX65
 }
+
+impl Decoded {
+    /// The instruction `opcode` encodes, if it is one of the CHIP-8 ones.
+    pub(crate) fn from_opcode(opcode: u16) -> Option<Decoded> {
+        let nibble3 = ((opcode & 0xF000) >> 12) as u8;
+        let nibble2 = ((opcode & 0x0F00) >> 8) as u8;
+        let nibble1 = ((opcode & 0x00F0) >> 4) as u8;
+        let nibble0 = (opcode & 0x000F) as u8;
+
+        let decoded = match (nibble3, nibble2, nibble1, nibble0) {
+            (0, 0, 0, 0)        => Decoded::NOP,
+            (0, 0, 0xE, 0)      => Decoded::ClearScreen,
+            (0, 0, 0xE, 0xE)    => Decoded::RET,
+            (1, _, _, _)        => Decoded::Jump(opcode & 0x0FFF),
+            (2, _, _, _)        => Decoded::Call(opcode & 0x0FFF),
+            (3, _, _, _)        => Decoded::SkipEq(nibble2, (opcode & 0xFF) as u8),
+            (4, _, _, _)        => Decoded::SkipNeq(nibble2, (opcode & 0xFF) as u8),
+            (5, _, _, 0)        => Decoded::SkipEqReg(nibble2, nibble1),
+            (6, _, _, _)        => Decoded::SetReg(nibble2, (opcode & 0xFF) as u8),
+            (7, _, _, _)        => Decoded::AddReg(nibble2, (opcode & 0xFF) as u8),
+            (8, _, _, 0)        => Decoded::SetRegReg(nibble2, nibble1),
+            (8, _, _, 1)        => Decoded::Or(nibble2, nibble1),
+            (8, _, _, 2)        => Decoded::And(nibble2, nibble1),
+            (8, _, _, 3)        => Decoded::Xor(nibble2, nibble1),
+            (8, _, _, 4)        => Decoded::AddRegReg(nibble2, nibble1),
+            (8, _, _, 5)        => Decoded::SubRegReg(nibble2, nibble1),
+            (8, _, _, 6)        => Decoded::RightShift(nibble2, nibble1),
+            (8, _, _, 7)        => Decoded::SubRegRegRev(nibble2, nibble1),
+            (8, _, _, 0xE)      => Decoded::LeftShift(nibble2, nibble1),
+            (9, _, _, 0)        => Decoded::SkipNeqReg(nibble2, nibble1),
+            (0xA, _, _, _)      => Decoded::SetIReg(opcode & 0x0FFF),
+            (0xB, _, _, _)      => Decoded::JumpOffset(opcode & 0x0FFF),
+            (0xC, _, _, _)      => Decoded::Rand(nibble2, (opcode & 0xFF) as u8),
+            (0xD, _, _, _)      => Decoded::Draw(nibble2, nibble1, nibble0),
+            (0xE, _, 9, 0xE)    => Decoded::SkipKey(nibble2),
+            (0xE, _, 0xA, 1)    => Decoded::SkipNKey(nibble2),
+            (0xF, _, 0, 7)      => Decoded::GetDelay(nibble2),
+            (0xF, _, 0, 0xA)    => Decoded::WaitKey(nibble2),
+            (0xF, _, 1, 5)      => Decoded::SetDelay(nibble2),
+            (0xF, _, 1, 8)      => Decoded::SetSound(nibble2),
+            (0xF, _, 1, 0xE)    => Decoded::AddIReg(nibble2),
+            (0xF, _, 2, 9)      => Decoded::SetIRegFont(nibble2),
+            (0xF, _, 3, 3)      => Decoded::StoreBCD(nibble2),
+            (0xF, _, 5, 5)      => Decoded::StoreRegsToMem(nibble2),
+            (0xF, _, 6, 5)      => Decoded::LoadMemToRegs(nibble2),
+            (_, _, _, _) => return None,
+        };
+        Some(decoded)
+    }
+}
@@ -1,13 +1,57 @@
-use std::collections::VecDeque;
-use rand::random;
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
 mod instructions;
 mod font;
+mod timing;
+mod display;
+mod quirks;
+mod game;
+mod peripherals;
+#[cfg(feature = "std")]
+mod png;
+#[cfg(feature = "std")]
+mod record;
+#[cfg(feature = "std")]
+mod env;
+#[cfg(feature = "std")]
+mod games;
+#[cfg(feature = "std")]
+mod database;
+#[cfg(feature = "std")]
+mod analysis;
+#[cfg(feature = "std")]
+pub mod cli;
+mod rng;
+mod state;
+mod fault;
+#[cfg(test)]
+mod tests;
 
 use instructions::Decoded;
+use game::GameTracker;
 use font::{FONTSET, FONTSET_SIZE};
-
-use rodio::{OutputStream, Sink, Source};
+use timing::{vip_cycles, VIP_CYCLES_FOR_PROGRAM};
+
+pub use timing::Timing;
+pub use display::{DirtyRect, Framebuffer};
+pub use quirks::Quirks;
+pub use game::{Condition, Encoding, GameRules, GameStatus, GameValue, RamValue};
+pub use peripherals::{Buzzer, DefaultBuzzer, Display, Keypad, Keys, Rng, SilentBuzzer};
+#[cfg(feature = "audio")]
+pub use peripherals::RodioBuzzer;
+pub use rng::XorShiftRng;
+pub use state::{StateError, STATE_SIZE};
+pub use fault::Fault;
+#[cfg(feature = "std")]
+pub use record::{BeepSynth, GifRecorder, RawVideoRecorder, Recording, WavRecorder, AUDIO_SAMPLE_RATE};
+#[cfg(feature = "std")]
+pub use env::{Env, GameScore, Observation, Reward, ScoreDelta, Step};
+#[cfg(feature = "std")]
+pub use games::{find_game, parse_games, GameInfo};
+#[cfg(feature = "std")]
+pub use database::{KeyHints, RomColors, RomDatabase, RomInfo};
+#[cfg(feature = "std")]
+pub use analysis::{analyze_rom, Finding, Platform, RomAnalysis};
 
 // The following are public because they are used in the main.rs file
 pub const SCREEN_WIDTH: usize = 64;
@@ -19,43 +63,87 @@ const STACK_SIZE: usize = 16;
 const NUM_KEYS: usize = 16;
 
 const START_ADDR: u16 = 0x200; // 512, CHIP-8 programs start at this address
-
-pub struct Emu {
+/// Largest ROM `load_rom` accepts: everything from the start address to the
+/// end of RAM.
+pub const MAX_ROM_SIZE: usize = RAM_SIZE - START_ADDR as usize;
+
+const DEFAULT_BEEP_PITCH: f32 = 440.0; // Hz
+const DEFAULT_BEEP_VOLUME: f32 = 0.2;
+
+/// The CHIP-8 machine. The peripherals are type parameters, so the same core
+/// drives the in-memory defaults frontends use or custom hardware and mocks,
+/// see `with_peripherals`. It clones cheaply (a few KB copied) when the
+/// peripherals do, e.g. `HeadlessEmu`.
+#[derive(Clone)]
+pub struct Emu<D = Framebuffer, K = Keys, B = DefaultBuzzer, R = XorShiftRng> {
     pc: u16, // program counter
     ram: [u8; RAM_SIZE],
-    screen: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
+    screen: D,
     v_reg: [u8; NUM_REGS], // general purpose registers
     i_reg: u16, // index register
-    stack: VecDeque<u16>,
-    keys: [bool; NUM_KEYS], // keypad
+    stack: [u16; STACK_SIZE],
+    sp: usize, // number of addresses on the stack
+    keys: K, // keypad
     dt: u8, // delay timer
     st: u8, // sound timer
 
-    sink: Option<Sink>,    // Audio sink
-    _stream: Option<OutputStream>,  // Audio device
+    quirks: Quirks,
+    timing: Timing,
+    cycle_budget: i32, // VIP machine cycles left in the current frame (negative = debt)
+    vblank: bool, // true until the first instruction after a frame starts
+    instruction_count: u64, // instructions executed since reset
+
+    rng: R,             // For CXNN
+    buzzer: B,
     is_beeping: bool,   // Is the sound timer beeping?
+    beep_pitch: f32,
+    beep_volume: f32,
+
+    game: GameTracker,
 }
 
 impl Emu {
+    /// A machine with the default peripherals: screen and keys in memory,
+    /// and the beep through rodio with the `audio` feature.
     pub fn new() -> Self {
-        let (stream, sink) = match OutputStream::try_default() {
-            Ok((stream, handle)) => (Some(stream), Some(Sink::try_new(&handle).unwrap())),
-            Err(_) => (None, None),
-        };
+        Self::with_peripherals(Framebuffer::new(), Keys::default(), DefaultBuzzer::default(), XorShiftRng::default())
+    }
+}
 
-        let mut my_emu: Emu = Self {
+/// A machine without audio, which can be cloned, e.g. to run many copies in
+/// parallel. See `Env`.
+pub type HeadlessEmu = Emu<Framebuffer, Keys, SilentBuzzer, XorShiftRng>;
+
+impl HeadlessEmu {
+    pub fn headless() -> Self {
+        Self::with_peripherals(Framebuffer::new(), Keys::default(), SilentBuzzer, XorShiftRng::default())
+    }
+}
+
+impl<D: Display, K: Keypad, B: Buzzer, R: Rng> Emu<D, K, B, R> {
+    pub fn with_peripherals(screen: D, keys: K, buzzer: B, rng: R) -> Self {
+        let mut my_emu = Self {
             pc: START_ADDR,
             ram: [0; RAM_SIZE],
-            screen: [false; SCREEN_WIDTH * SCREEN_HEIGHT],
+            screen,
             v_reg: [0; NUM_REGS],
             i_reg: 0,
-            stack: VecDeque::with_capacity(STACK_SIZE),
-            keys: [false; NUM_KEYS],
+            stack: [0; STACK_SIZE],
+            sp: 0,
+            keys,
             dt: 0,
             st: 0,
-            sink,
-            _stream: stream,
+            quirks: Quirks::default(),
+            timing: Timing::default(),
+            cycle_budget: 0,
+            vblank: false,
+            instruction_count: 0,
+            rng,
+            buzzer,
             is_beeping: false,
+            beep_pitch: DEFAULT_BEEP_PITCH,
+            beep_volume: DEFAULT_BEEP_VOLUME,
+            game: GameTracker::default(),
         };
 
         // Load fontset into memory
@@ -67,91 +155,237 @@ impl Emu {
     pub fn reset(&mut self) {
         self.pc = START_ADDR;
         self.ram = [0; RAM_SIZE];
-        self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        self.screen.reset();
         self.v_reg = [0; NUM_REGS];
         self.i_reg = 0;
-        self.stack = VecDeque::with_capacity(STACK_SIZE);
-        self.keys = [false; NUM_KEYS];
+        self.stack = [0; STACK_SIZE];
+        self.sp = 0;
+        self.keys.reset();
         self.dt = 0;
         self.st = 0;
+        self.cycle_budget = 0;
+        self.vblank = false;
+        self.instruction_count = 0;
         self.ram[..FONTSET_SIZE].copy_from_slice(&FONTSET);
 
-        self.stop_beep();
+        self.buzzer.stop();
         self.is_beeping = false;
+        self.game = GameTracker::new(self.game.rules);
+        self.update_game_status();
     }
 
+    /// Runs one instruction. Panics if it can't run, see `try_tick`.
     pub fn tick(&mut self) {
-        // Fetch opcode
-        let opcode = self.fetch();
-        // Decode opcode
-        let decoded = self.decode(opcode);
-        // Execute opcode
-        self.execute(decoded);
+        if let Err(fault) = self.try_tick() {
+            panic!("{fault}");
+        }
     }
 
-    fn fetch(&mut self) -> u16 {
-        let h_byte = self.ram[self.pc as usize] as u16;
-        let l_byte = self.ram[(self.pc + 1) as usize] as u16;
+    /// Runs one instruction, or leaves the machine as it is if the
+    /// instruction can't run.
+    pub fn try_tick(&mut self) -> Result<(), Fault> {
+        let decoded = self.next_instruction()?;
         self.pc += 2;
+        self.execute(decoded);
+        Ok(())
+    }
+
+    /// Runs one 60 Hz frame: the instructions that fit in it according to
+    /// the timing model, followed by a timer tick. `ipf` is only used by
+    /// `Timing::Instructions`. Panics if an instruction can't run, see
+    /// `try_run_frame`.
+    pub fn run_frame(&mut self, ipf: usize) {
+        if let Err(fault) = self.try_run_frame(ipf) {
+            panic!("{fault}");
+        }
+    }
+
+    /// Like `run_frame`, but stops before an instruction that can't run,
+    /// without ticking the timers.
+    pub fn try_run_frame(&mut self, ipf: usize) -> Result<(), Fault> {
+        match self.timing {
+            Timing::Instructions => {
+                for _ in 0..ipf {
+                    self.try_tick()?;
+                }
+            },
+            Timing::CosmacVip => self.run_vip_frame()?,
+        }
+        self.tick_timers();
+        self.update_game_status();
+        Ok(())
+    }
+
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+        self.cycle_budget = 0;
+    }
+
+    pub fn get_timing(&self) -> Timing {
+        self.timing
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    pub fn get_quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// Number of instructions executed since the last reset, e.g. for an
+    /// instructions-per-second counter.
+    pub fn get_instruction_count(&self) -> u64 {
+        self.instruction_count
+    }
+
+    /// True while the sound timer is running, i.e. the beep is audible.
+    pub fn is_beeping(&self) -> bool {
+        self.is_beeping
+    }
+
+    /// The beep's pitch in Hz and volume, as set with `set_beep`.
+    pub fn get_beep(&self) -> (f32, f32) {
+        (self.beep_pitch, self.beep_volume)
+    }
+
+    /// Sets the tone played while the sound timer is active. `volume` is the
+    /// amplitude of the sine wave, from 0 (silent) to 1.
+    pub fn set_beep(&mut self, pitch: f32, volume: f32) {
+        self.beep_pitch = pitch;
+        self.beep_volume = volume.clamp(0.0, 1.0);
+    }
+
+    /// Tells the emulator where the running game keeps its score, lives and
+    /// game over state, see `find_game`. Kept across resets, like quirks.
+    pub fn set_game_rules(&mut self, rules: Option<GameRules>) {
+        self.game = GameTracker::new(rules);
+        self.update_game_status();
+    }
+
+    pub fn get_game_rules(&self) -> Option<GameRules> {
+        self.game.rules
+    }
+
+    /// The game's score, lives and whether it ended, evaluated at the end of
+    /// every frame. `None` without game rules. The game only counts as over
+    /// (or won) once the condition has been false since the last reset, as
+    /// it often holds before the game sets up its variables.
+    pub fn game_status(&self) -> Option<GameStatus> {
+        self.game.status()
+    }
+
+    fn update_game_status(&mut self) {
+        if let Some(rules) = self.game.rules {
+            let status = rules.evaluate(self);
+            self.game.update(status);
+        }
+    }
+
+    pub fn display(&self) -> &D {
+        &self.screen
+    }
 
-        return (h_byte << 8) | l_byte; // Big-endian
-    }
-
-    fn decode(&mut self, opcode: u16) -> Decoded {
-        // TODO
-        let nibble3 = ((opcode & 0xF000) >> 12) as u8;
-        let nibble2 = ((opcode & 0x0F00) >> 8) as u8;
-        let nibble1 = ((opcode & 0x00F0) >> 4) as u8;
-        let nibble0 = (opcode & 0x000F) as u8;
-
-        match (nibble3, nibble2, nibble1, nibble0) {
-            (0, 0, 0, 0)        => Decoded::NOP,
-            (0, 0, 0xE, 0)      => Decoded::ClearScreen,
-            (0, 0, 0xE, 0xE)    => Decoded::RET,
-            (1, _, _, _)        => Decoded::Jump(opcode & 0x0FFF),
-            (2, _, _, _)        => Decoded::Call(opcode & 0x0FFF),
-            (3, _, _, _)        => Decoded::SkipEq(nibble2, (opcode & 0xFF) as u8),
-            (4, _, _, _)        => Decoded::SkipNeq(nibble2, (opcode & 0xFF) as u8),
-            (5, _, _, 0)        => Decoded::SkipEqReg(nibble2, nibble1),
-            (6, _, _, _)        => Decoded::SetReg(nibble2, (opcode & 0xFF) as u8),
-            (7, _, _, _)        => Decoded::AddReg(nibble2, (opcode & 0xFF) as u8),
-            (8, _, _, 0)        => Decoded::SetRegReg(nibble2, nibble1),
-            (8, _, _, 1)        => Decoded::Or(nibble2, nibble1),
-            (8, _, _, 2)        => Decoded::And(nibble2, nibble1),
-            (8, _, _, 3)        => Decoded::Xor(nibble2, nibble1),
-            (8, _, _, 4)        => Decoded::AddRegReg(nibble2, nibble1),
-            (8, _, _, 5)        => Decoded::SubRegReg(nibble2, nibble1),
-            (8, _, _, 6)        => Decoded::RightShift(nibble2),
-            (8, _, _, 7)        => Decoded::SubRegRegRev(nibble2, nibble1),
-            (8, _, _, 0xE)      => Decoded::LeftShift(nibble2),
-            (9, _, _, 0)        => Decoded::SkipNeqReg(nibble2, nibble1),
-            (0xA, _, _, _)      => Decoded::SetIReg(opcode & 0x0FFF),
-            (0xB, _, _, _)      => Decoded::JumpOffset(opcode & 0x0FFF),
-            (0xC, _, _, _)      => Decoded::Rand(nibble2, (opcode & 0xFF) as u8),
-            (0xD, _, _, _)      => Decoded::Draw(nibble2, nibble1, nibble0),
-            (0xE, _, 9, 0xE)    => Decoded::SkipKey(nibble2),
-            (0xE, _, 0xA, 1)    => Decoded::SkipNKey(nibble2),
-            (0xF, _, 0, 7)      => Decoded::GetDelay(nibble2),
-            (0xF, _, 0, 0xA)    => Decoded::WaitKey(nibble2),
-            (0xF, _, 1, 5)      => Decoded::SetDelay(nibble2),
-            (0xF, _, 1, 8)      => Decoded::SetSound(nibble2),
-            (0xF, _, 1, 0xE)    => Decoded::AddIReg(nibble2),
-            (0xF, _, 2, 9)      => Decoded::SetIRegFont(nibble2),
-            (0xF, _, 3, 3)      => Decoded::StoreBCD(nibble2),
-            (0xF, _, 5, 5)      => Decoded::StoreRegsToMem(nibble2),
-            (0xF, _, 6, 5)      => Decoded::LoadMemToRegs(nibble2),
-            (_, _, _, _) => unimplemented!("Unknown opcode: {:#06x}", opcode),
+    pub fn display_mut(&mut self) -> &mut D {
+        &mut self.screen
+    }
+
+    pub fn keypad(&self) -> &K {
+        &self.keys
+    }
+
+    pub fn keypad_mut(&mut self) -> &mut K {
+        &mut self.keys
+    }
+
+    pub fn buzzer(&self) -> &B {
+        &self.buzzer
+    }
+
+    pub fn buzzer_mut(&mut self) -> &mut B {
+        &mut self.buzzer
+    }
+
+    pub fn rng_mut(&mut self) -> &mut R {
+        &mut self.rng
+    }
+
+    fn run_vip_frame(&mut self) -> Result<(), Fault> {
+        // An instruction that overran the previous frame is paid for here
+        self.cycle_budget += VIP_CYCLES_FOR_PROGRAM;
+        self.vblank = true;
+
+        while self.cycle_budget > 0 {
+            let pc = self.pc;
+            let decoded = self.next_instruction()?;
+
+            // DXYN waits for the display interrupt: the rest of the frame is
+            // spent idling and the sprite is drawn right after vblank.
+            if matches!(decoded, Decoded::Draw(..)) && !self.vblank {
+                self.cycle_budget = 0;
+                break;
+            }
+
+            self.pc += 2;
+            self.execute(decoded);
+            self.vblank = false;
+            let skipped = self.pc == pc + 4;
+            self.cycle_budget -= vip_cycles(decoded, &self.v_reg, skipped);
         }
+        Ok(())
+    }
+
+    // Fetches and decodes the instruction at PC, checking that it can run
+    // in the current state, so `execute` can't fail
+    fn next_instruction(&self) -> Result<Decoded, Fault> {
+        let pc = self.pc;
+        if pc as usize + 1 >= RAM_SIZE {
+            return Err(Fault::PcOutOfRange { pc });
+        }
+        let opcode = u16::from_be_bytes([self.ram[pc as usize], self.ram[pc as usize + 1]]);
+        let decoded = Decoded::from_opcode(opcode).ok_or(Fault::UnknownOpcode { pc, opcode })?;
+
+        // bytes read or written from I
+        let from_i = match decoded {
+            Decoded::RET if self.sp == 0 => return Err(Fault::StackUnderflow { pc }),
+            Decoded::SkipKey(x) | Decoded::SkipNKey(x) if self.v_reg[x as usize] as usize >= NUM_KEYS => {
+                return Err(Fault::InvalidKey { pc, key: self.v_reg[x as usize] });
+            },
+            Decoded::Draw(_, y, rows) => {
+                // clipped rows aren't read
+                let y_pos = self.v_reg[y as usize] as usize % SCREEN_HEIGHT;
+                if self.quirks.clip_sprites { (rows as usize).min(SCREEN_HEIGHT - y_pos) } else { rows as usize }
+            },
+            Decoded::StoreBCD(_) => 3,
+            Decoded::StoreRegsToMem(x) | Decoded::LoadMemToRegs(x) => x as usize + 1,
+            _ => 0,
+        };
+        if from_i > 0 && self.i_reg as usize + from_i > RAM_SIZE {
+            return Err(Fault::MemoryOutOfRange { pc, i: self.i_reg });
+        }
+        Ok(decoded)
     }
 
     fn execute(&mut self, instruction: Decoded) {
+        self.instruction_count += 1;
         match instruction {
             Decoded::NOP             => (),
-            Decoded::ClearScreen     => self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT],
-            Decoded::RET             => self.pc = self.stack.pop_back().expect("Stack underflow on RET"),
+            Decoded::ClearScreen     => self.screen.clear(),
+            Decoded::RET             => {
+                self.sp -= 1;
+                self.pc = self.stack[self.sp];
+            },
             Decoded::Jump(addr) => self.pc = addr,
             Decoded::Call(addr) => {
-                self.stack.push_back(self.pc);
+                // past 16 levels the oldest return address is dropped, so
+                // deep programs keep running as long as they don't return
+                // that far
+                if self.sp == STACK_SIZE {
+                    self.stack.copy_within(1.., 0);
+                    self.sp -= 1;
+                }
+                self.stack[self.sp] = self.pc;
+                self.sp += 1;
                 self.pc = addr;
             },
             Decoded::SkipEq(x, value) => {
@@ -180,12 +414,21 @@ impl Emu {
             },
             Decoded::Or(x, y) => {
                 self.v_reg[x as usize] |= self.v_reg[y as usize];
+                if self.quirks.vf_reset {
+                    self.v_reg[NUM_REGS - 1] = 0;
+                }
             },
             Decoded::And(x, y) => {
                 self.v_reg[x as usize] &= self.v_reg[y as usize];
+                if self.quirks.vf_reset {
+                    self.v_reg[NUM_REGS - 1] = 0;
+                }
             },
             Decoded::Xor(x, y) => {
                 self.v_reg[x as usize] ^= self.v_reg[y as usize];
+                if self.quirks.vf_reset {
+                    self.v_reg[NUM_REGS - 1] = 0;
+                }
             },
             Decoded::AddRegReg(x, y) => {
                 let (result, overflow) = self.v_reg[x as usize].overflowing_add(self.v_reg[y as usize]);
@@ -193,22 +436,27 @@ impl Emu {
                 self.v_reg[NUM_REGS - 1] = overflow as u8; 
             },
             Decoded::SubRegReg(x, y ) => {
-                let (result, overflow) = self.v_reg[x as usize].overflowing_sub(self.v_reg[y as usize]);
+                let (result, borrow) = self.v_reg[x as usize].overflowing_sub(self.v_reg[y as usize]);
                 self.v_reg[x as usize] = result;
-                self.v_reg[NUM_REGS - 1] = overflow as u8;
+                self.v_reg[NUM_REGS - 1] = !borrow as u8; // VF is 1 when there is NO borrow
             },
-            Decoded::RightShift(x) => {
-                self.v_reg[NUM_REGS - 1] = self.v_reg[x as usize] & 0x1;
-                self.v_reg[x as usize] >>= 1;
+            Decoded::RightShift(x, y) => {
+                let source = if self.quirks.shift_uses_vy { y } else { x };
+                let value = self.v_reg[source as usize];
+                // the flag is written last, so it wins when x is VF
+                self.v_reg[x as usize] = value >> 1;
+                self.v_reg[NUM_REGS - 1] = value & 0x1;
             },
             Decoded::SubRegRegRev(x,y ) => {
-                let (result, overflow) = self.v_reg[y as usize].overflowing_sub(self.v_reg[x as usize]);
+                let (result, borrow) = self.v_reg[y as usize].overflowing_sub(self.v_reg[x as usize]);
                 self.v_reg[x as usize] = result;
-                self.v_reg[NUM_REGS - 1] = overflow as u8;
+                self.v_reg[NUM_REGS - 1] = !borrow as u8;
             },
-            Decoded::LeftShift(x) => {
-                self.v_reg[NUM_REGS - 1] = (self.v_reg[x as usize] & 0x80) >> 7;
-                self.v_reg[x as usize] <<= 1;
+            Decoded::LeftShift(x, y) => {
+                let source = if self.quirks.shift_uses_vy { y } else { x };
+                let value = self.v_reg[source as usize];
+                self.v_reg[x as usize] = value << 1;
+                self.v_reg[NUM_REGS - 1] = (value & 0x80) >> 7;
             },
             Decoded::SkipNeqReg(x, y ) => {
                 if self.v_reg[x as usize] != self.v_reg[y as usize] {
@@ -219,10 +467,12 @@ impl Emu {
                 self.i_reg = addr;
             },
             Decoded::JumpOffset(offset) => {
-                self.pc = offset + self.v_reg[0] as u16;
+                // BXNN: the register is the high nibble of the address
+                let reg = if self.quirks.jump_uses_vx { (offset >> 8) as usize } else { 0 };
+                self.pc = offset + self.v_reg[reg] as u16;
             },
             Decoded::Rand(x, value) => {
-                self.v_reg[x as usize] = random::<u8>() & value;
+                self.v_reg[x as usize] = self.rng.next_u8() & value;
             },
             Decoded::Draw(x,y , nb_rows) => {
                 let x_pos = self.v_reg[x as usize] as u16;
@@ -230,47 +480,31 @@ impl Emu {
 
                 self.v_reg[NUM_REGS - 1] = 0; // Reset VF
 
-                // iterate over each row of the sprite
-                for row in 0..nb_rows {
-                    let sprite_row = self.ram[self.i_reg as usize + row as usize];
-
-                    // iterate over each pixel(bit) in the row
-                    for col in 0..8 {
-                        let sprite_bit = (sprite_row >> (7 - col)) & 0x1; // MSB on the left
-                        /*
-                        sprite   screen  |  new screen
-                        0        0       |  0
-                        0        1       |  1
-                        1        0       |  1
-                        1        1       |  0 (collision)
-
-                        in the first two cases the screen bit is XORed with 0, so it remains the same
-                        in the last two cases the screen bit is XORed with 1, so it changes
-                        */
-                        if sprite_bit != 0 {
-                            let x_final = (x_pos as usize + col as usize) % SCREEN_WIDTH;
-                            let y_final = (y_pos as usize + row as usize)   % SCREEN_HEIGHT;
-
-                            let screen_idx = y_final * SCREEN_WIDTH + x_final;
-
-                            // if the screen bit was 1 (and sprite bit was 1), this means collision, set VF to 1
-                            if self.screen[screen_idx] {
-                                self.v_reg[NUM_REGS - 1] = 1;
-                            }
-
-                            // XOR the sprite bit with the screen bit
-                            self.screen[screen_idx] ^= true;
-                        }
+                // the starting position always wraps, the sprite itself only
+                // wraps without the clipping quirk
+                let x_pos = x_pos as usize % SCREEN_WIDTH;
+                let y_pos = y_pos as usize % SCREEN_HEIGHT;
+                let clip = self.quirks.clip_sprites;
+
+                // XOR each sprite row into its screen row; any lit pixel turned
+                // off means collision
+                for row in 0..nb_rows as usize {
+                    if clip && y_pos + row >= SCREEN_HEIGHT {
+                        break;
+                    }
+                    let sprite_row = self.ram[self.i_reg as usize + row];
+                    if self.screen.draw_sprite_row(x_pos, y_pos + row, sprite_row, clip) {
+                        self.v_reg[NUM_REGS - 1] = 1;
                     }
                 }
             },
             Decoded::SkipKey(x) => {
-                if self.keys[self.v_reg[x as usize] as usize] {
+                if self.keys.is_pressed(self.v_reg[x as usize] as usize) {
                     self.pc += 2;
                 }
             },
             Decoded::SkipNKey(x) => {
-                if !self.keys[self.v_reg[x as usize] as usize] {
+                if !self.keys.is_pressed(self.v_reg[x as usize] as usize) {
                     self.pc += 2;
                 }
             },
@@ -279,8 +513,8 @@ impl Emu {
             },
             Decoded::WaitKey(x) => {
                 let mut key_pressed = false;
-                for (i, key) in self.keys.iter().enumerate() {
-                    if *key { // if the key is pressed
+                for i in 0..NUM_KEYS {
+                    if self.keys.is_pressed(i) {
                         self.v_reg[x as usize] = i as u8;
                         key_pressed = true;
                         break;
@@ -299,7 +533,7 @@ impl Emu {
                 self.st = self.v_reg[x as usize];
             },
             Decoded::AddIReg(x) => {
-                self.i_reg += self.v_reg[x as usize] as u16;
+                self.i_reg = self.i_reg.wrapping_add(self.v_reg[x as usize] as u16);
             },
             Decoded::SetIRegFont(x) => { 
                 // store the address of v[x] sprite in I. Each sprite is 5 bytes long.
@@ -316,11 +550,17 @@ impl Emu {
                 for i in 0..=x {
                     self.ram[self.i_reg as usize + i as usize] = self.v_reg[i as usize];
                 }
+                if self.quirks.memory_increment {
+                    self.i_reg += x as u16 + !self.quirks.memory_increment_by_x as u16;
+                }
             },
             Decoded::LoadMemToRegs(x) => {
                 for i in 0..=x {
                     self.v_reg[i as usize] = self.ram[self.i_reg as usize + i as usize];
                 }
+                if self.quirks.memory_increment {
+                    self.i_reg += x as u16 + !self.quirks.memory_increment_by_x as u16;
+                }
            },
         }
     }
@@ -333,49 +573,81 @@ impl Emu {
         if self.st > 0 {
             // Beep: I'll implement this later
             if !self.is_beeping {
-                self.play_beep();
+                self.buzzer.start(self.beep_pitch, self.beep_volume);
                 self.is_beeping = true;
             }
             self.st -= 1;
         } else {
             // stop beep if timer is 0
             if self.is_beeping {
-                self.stop_beep();
+                self.buzzer.stop();
                 self.is_beeping = false;
             }
         }
     }
 
-    pub fn get_screen(&self) -> &[bool] {
-        &self.screen
+    pub fn get_pixel(&self, x: usize, y: usize) -> bool {
+        self.screen.pixel(x, y)
     }
 
-    pub fn keypress(&mut self, key: usize, pressed: bool) {
-        if key < NUM_KEYS {
-            self.keys[key] = pressed;
-        }
+    /// The whole 4 KB of memory, e.g. to read a game's score.
+    pub fn get_ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    /// V0 to VF.
+    pub fn get_registers(&self) -> &[u8] {
+        &self.v_reg
+    }
+
+    /// Encodes the screen as a PNG image, drawn in the given colours with
+    /// each CHIP-8 pixel as a `scale` x `scale` block (1 = native 64x32).
+    #[cfg(feature = "std")]
+    pub fn screenshot_png(&self, scale: usize, background: [u8; 3], foreground: [u8; 3]) -> Vec<u8> {
+        let scale = scale.max(1);
+        png::encode_two_color(SCREEN_WIDTH * scale, SCREEN_HEIGHT * scale, [background, foreground], |x, y| {
+            self.get_pixel(x / scale, y / scale)
+        })
     }
 
     pub fn load_rom(&mut self, rom: &[u8]) {
         let start = START_ADDR as usize;
         let end = start + rom.len();
         self.ram[start..end].copy_from_slice(rom);
+        self.update_game_status();
     }
+}
 
-    fn play_beep(&self) {
-        if let Some(sink) = &self.sink {
-            let source = rodio::source::SineWave::new(440.0)
-                .take_duration(std::time::Duration::from_secs_f32(0.1))
-                .amplify(0.2);
-                
-            sink.append(source);
-        }
+impl<K: Keypad, B: Buzzer, R: Rng> Emu<Framebuffer, K, B, R> {
+    /// The display as packed rows, see `Framebuffer`.
+    pub fn get_screen(&self) -> &[u64] {
+        self.screen.rows()
     }
 
-    fn stop_beep(&self) {
-        if let Some(sink) = &self.sink {
-            sink.stop();
-        }
+    /// The part of the screen that changed since the last call, if any.
+    /// Frontends can skip redrawing when this returns `None`.
+    pub fn take_dirty(&mut self) -> Option<DirtyRect> {
+        self.screen.take_dirty()
+    }
+}
+
+impl<D: Display, B: Buzzer, R: Rng> Emu<D, Keys, B, R> {
+    pub fn keypress(&mut self, key: usize, pressed: bool) {
+        self.keys.set(key, pressed);
+    }
+}
+
+impl<D: Display, K: Keypad, B: Buzzer> Emu<D, K, B, XorShiftRng> {
+    /// Reseeds the generator behind CXNN, for reproducible runs. Without the
+    /// `rand` feature every emulator starts from the same fixed seed.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng.seed(seed);
     }
 }
 
+/// SHA-1 of a ROM image as lowercase hex, used to identify ROMs regardless of
+/// their file name.
+#[cfg(feature = "std")]
+pub fn rom_hash(rom: &[u8]) -> String {
+    sha1_smol::Sha1::from(rom).digest().to_string()
+}
@@ -0,0 +1,134 @@
+// The hardware around the CPU, as traits so that `Emu` can drive a real LCD,
+// keypad matrix or buzzer, or mocks in tests. The defaults keep everything
+// in memory, and with the `audio` feature play the beep through rodio.
+
+#[cfg(feature = "audio")]
+use rodio::{OutputStream, Sink, Source};
+
+use crate::NUM_KEYS;
+
+/// The 64x32 monochrome screen. `Framebuffer` is the in-memory one.
+pub trait Display {
+    /// Turns every pixel off (00E0).
+    fn clear(&mut self);
+
+    /// XORs an 8 pixel sprite row at (x, y), which is on screen, wrapping
+    /// around the right edge or, with `clip`, dropping the pixels past it.
+    /// Returns true if a lit pixel was turned off (collision).
+    fn draw_sprite_row(&mut self, x: usize, y: usize, sprite_row: u8, clip: bool) -> bool;
+
+    fn pixel(&self, x: usize, y: usize) -> bool;
+
+    /// Called by `Emu::reset`, clears the screen unless overridden.
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+/// The 16 key hexadecimal keypad.
+pub trait Keypad {
+    /// Whether key 0 to 15 is held down.
+    fn is_pressed(&self, key: usize) -> bool;
+
+    /// Called by `Emu::reset`.
+    fn reset(&mut self) {}
+}
+
+/// Sound output, on while the sound timer runs.
+pub trait Buzzer {
+    /// `pitch` is in Hz and `volume` from 0 to 1, as set with `Emu::set_beep`.
+    fn start(&mut self, pitch: f32, volume: f32);
+
+    fn stop(&mut self);
+}
+
+/// Random numbers for CXNN. `XorShiftRng` is the built-in generator, and any
+/// `fn() -> u8` works too, e.g. one reading a hardware RNG.
+pub trait Rng {
+    fn next_u8(&mut self) -> u8;
+}
+
+impl Rng for fn() -> u8 {
+    fn next_u8(&mut self) -> u8 {
+        self()
+    }
+}
+
+/// Keypad state in memory, set with `Emu::keypress`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Keys {
+    pressed: [bool; NUM_KEYS],
+}
+
+impl Keys {
+    /// Out of range keys are ignored.
+    pub fn set(&mut self, key: usize, pressed: bool) {
+        if key < NUM_KEYS {
+            self.pressed[key] = pressed;
+        }
+    }
+}
+
+impl Keypad for Keys {
+    fn is_pressed(&self, key: usize) -> bool {
+        self.pressed[key]
+    }
+
+    fn reset(&mut self) {
+        self.pressed = [false; NUM_KEYS];
+    }
+}
+
+/// Plays nothing, for frontends making the sound themselves from
+/// `Emu::is_beeping`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SilentBuzzer;
+
+impl Buzzer for SilentBuzzer {
+    fn start(&mut self, _pitch: f32, _volume: f32) {}
+
+    fn stop(&mut self) {}
+}
+
+/// Plays a sine wave on the default output device, or nothing if there is
+/// none.
+#[cfg(feature = "audio")]
+pub struct RodioBuzzer {
+    sink: Option<Sink>,
+    _stream: Option<OutputStream>, // the device, closed when dropped
+}
+
+#[cfg(feature = "audio")]
+impl Default for RodioBuzzer {
+    fn default() -> Self {
+        match OutputStream::try_default() {
+            Ok((stream, handle)) => Self { sink: Some(Sink::try_new(&handle).unwrap()), _stream: Some(stream) },
+            Err(_) => Self { sink: None, _stream: None },
+        }
+    }
+}
+
+#[cfg(feature = "audio")]
+impl Buzzer for RodioBuzzer {
+    fn start(&mut self, pitch: f32, volume: f32) {
+        if let Some(sink) = &self.sink {
+            let source = rodio::source::SineWave::new(pitch)
+                .take_duration(std::time::Duration::from_secs_f32(0.1))
+                .amplify(volume);
+            sink.append(source);
+        }
+    }
+
+    fn stop(&mut self) {
+        if let Some(sink) = &self.sink {
+            sink.stop();
+        }
+    }
+}
+
+/// The buzzer `Emu::new` uses: `RodioBuzzer` with the `audio` feature,
+/// `SilentBuzzer` without.
+#[cfg(feature = "audio")]
+pub type DefaultBuzzer = RodioBuzzer;
+#[cfg(not(feature = "audio"))]
+pub type DefaultBuzzer = SilentBuzzer;
@@ -0,0 +1,59 @@
+/// Behaviours that differ between CHIP-8 interpreters. Games written for one
+/// of them often break on the others, so they can be toggled one by one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Quirks {
+    /// 8XY1, 8XY2 and 8XY3 reset VF to 0.
+    pub vf_reset: bool,
+    /// FX55 and FX65 leave I pointing after the last register stored/loaded.
+    pub memory_increment: bool,
+    /// With `memory_increment`, I is left on the last register instead of
+    /// after it (I += X), as on CHIP-48 and SUPER-CHIP 1.0.
+    pub memory_increment_by_x: bool,
+    /// 8XY6 and 8XYE shift VY into VX instead of shifting VX in place.
+    pub shift_uses_vy: bool,
+    /// BNNN is read as BXNN and jumps to XNN + VX instead of NNN + V0.
+    pub jump_uses_vx: bool,
+    /// Sprites are clipped at the screen edges instead of wrapping around.
+    /// The starting position still wraps.
+    pub clip_sprites: bool,
+}
+
+impl Quirks {
+    /// The original interpreter on the COSMAC VIP.
+    pub const COSMAC_VIP: Quirks = Quirks {
+        vf_reset: true,
+        memory_increment: true,
+        memory_increment_by_x: false,
+        shift_uses_vy: true,
+        jump_uses_vx: false,
+        clip_sprites: true,
+    };
+
+    /// SUPER-CHIP 1.1 on the HP48.
+    pub const SCHIP: Quirks = Quirks {
+        vf_reset: false,
+        memory_increment: false,
+        memory_increment_by_x: false,
+        shift_uses_vy: false,
+        jump_uses_vx: true,
+        clip_sprites: true,
+    };
+
+    /// Names accepted by `from_name`, in the order frontends should list them.
+    pub const PROFILES: [&'static str; 3] = ["default", "vip", "schip"];
+
+    /// Looks up a quirk profile by name: `default` (this emulator's own
+    /// behaviour, all quirks off), `vip` or `schip`.
+    pub fn from_name(name: &str) -> Option<Quirks> {
+        let is = |names: &[&str]| names.iter().any(|n| n.eq_ignore_ascii_case(name));
+        if is(&["default"]) {
+            Some(Quirks::default())
+        } else if is(&["vip", "chip8", "chip-8"]) {
+            Some(Quirks::COSMAC_VIP)
+        } else if is(&["schip", "superchip"]) {
+            Some(Quirks::SCHIP)
+        } else {
+            None
+        }
+    }
+}
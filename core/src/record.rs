@@ -0,0 +1,354 @@
+// Recording of the emulated screen and sound, for frontends: an animated GIF
+// and, for ffmpeg, raw RGB24 video plus a WAV file.
+
+use std::f32::consts::TAU;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::{Buzzer, Display, Emu, Framebuffer, Keypad, Rng, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+const FRAME_RATE: u32 = 60;
+pub const AUDIO_SAMPLE_RATE: u32 = 44100;
+const SAMPLES_PER_FRAME: usize = (AUDIO_SAMPLE_RATE / FRAME_RATE) as usize;
+
+// GIF delays are in centiseconds, and viewers slow down anything under 2
+const MIN_GIF_DELAY: u32 = 2;
+const LZW_MIN_CODE_SIZE: u8 = 2; // the smallest GIF allows, for our 2 colours
+const MAX_LZW_CODE: u16 = 4095;
+
+/// Writes an animated GIF, one `frame` call per emulated frame. Identical
+/// frames are merged into one with a longer delay, and frames too short for
+/// GIF players (most flicker) are dropped, keeping the total time exact.
+pub struct GifRecorder<W: Write> {
+    out: W,
+    scale: usize,
+    pending: Option<[u64; SCREEN_HEIGHT]>, // last frame, not written yet
+    ticks: u32,         // emulated frames recorded, written or not
+    delay_written: u32, // centiseconds of delay written so far
+}
+
+impl<W: Write> GifRecorder<W> {
+    /// Fails with `InvalidInput` if the scaled screen is wider than the
+    /// 65535 pixels a GIF can hold.
+    pub fn new(mut out: W, scale: usize, background: [u8; 3], foreground: [u8; 3]) -> io::Result<Self> {
+        let scale = scale.max(1);
+        let (width, height) = gif_size(scale)?;
+
+        out.write_all(b"GIF89a")?;
+        out.write_all(&width.to_le_bytes())?;
+        out.write_all(&height.to_le_bytes())?;
+        // global colour table of 2 entries, background colour 0, square pixels
+        out.write_all(&[0x80, 0, 0])?;
+        out.write_all(&background)?;
+        out.write_all(&foreground)?;
+        // loop forever
+        out.write_all(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+
+        Ok(Self { out, scale, pending: None, ticks: 0, delay_written: 0 })
+    }
+
+    pub fn frame<K: Keypad, B: Buzzer, R: Rng>(&mut self, emu: &Emu<Framebuffer, K, B, R>) -> io::Result<()> {
+        let mut rows = [0; SCREEN_HEIGHT];
+        rows.copy_from_slice(emu.get_screen());
+
+        match self.pending {
+            Some(pending) if pending != rows && self.delay_due() >= MIN_GIF_DELAY => {
+                self.write_pending()?;
+                self.pending = Some(rows);
+            },
+            // same frame, or the pending one was too short to show and the
+            // new one takes its place
+            _ => self.pending = Some(rows),
+        }
+        self.ticks += 1;
+        Ok(())
+    }
+
+    /// Writes the last frame and the GIF trailer, and returns the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.pending.is_some() {
+            self.write_pending()?;
+        }
+        self.out.write_all(&[0x3B])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    // Delay the pending frame gets if written now, so that the sum of the
+    // delays follows the emulated time
+    fn delay_due(&self) -> u32 {
+        (self.ticks * 100 + FRAME_RATE / 2) / FRAME_RATE - self.delay_written
+    }
+
+    fn write_pending(&mut self) -> io::Result<()> {
+        let rows = self.pending.take().unwrap();
+        let mut delay = self.delay_due().max(1);
+        self.delay_written += delay;
+
+        // a frame shows for at most 655.35 s, longer ones are repeated
+        while delay > 0 {
+            let part = delay.min(u16::MAX as u32);
+            self.write_image(&rows, part as u16)?;
+            delay -= part;
+        }
+        Ok(())
+    }
+
+    fn write_image(&mut self, rows: &[u64; SCREEN_HEIGHT], delay: u16) -> io::Result<()> {
+        // graphic control extension: keep the previous frame, delay
+        self.out.write_all(&[0x21, 0xF9, 0x04, 0x04])?;
+        self.out.write_all(&delay.to_le_bytes())?;
+        self.out.write_all(&[0, 0])?;
+
+        // image descriptor covering the whole screen, no local colour table
+        let width = SCREEN_WIDTH * self.scale;
+        let height = SCREEN_HEIGHT * self.scale;
+        self.out.write_all(&[0x2C, 0, 0, 0, 0])?;
+        self.out.write_all(&(width as u16).to_le_bytes())?;
+        self.out.write_all(&(height as u16).to_le_bytes())?;
+        self.out.write_all(&[0])?;
+
+        let scale = self.scale;
+        let pixels = (0..height).flat_map(|y| {
+            let row = rows[y / scale];
+            (0..width).map(move |x| (row >> (SCREEN_WIDTH - 1 - x / scale)) as u8 & 1)
+        });
+        let data = lzw_encode(pixels);
+
+        self.out.write_all(&[LZW_MIN_CODE_SIZE])?;
+        for block in data.chunks(255) {
+            self.out.write_all(&[block.len() as u8])?;
+            self.out.write_all(block)?;
+        }
+        self.out.write_all(&[0])
+    }
+}
+
+// Width and height of the GIF at `scale`, which has to fit in 16 bits
+fn gif_size(scale: usize) -> io::Result<(u16, u16)> {
+    let size = |pixels: usize| pixels.checked_mul(scale).and_then(|size| u16::try_from(size).ok());
+    match (size(SCREEN_WIDTH), size(SCREEN_HEIGHT)) {
+        (Some(width), Some(height)) => Ok((width, height)),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("scale {scale} is too large for a GIF"))),
+    }
+}
+
+// Packs variable-width codes, least significant bit first
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u32) {
+        self.bits |= (code as u32) << self.count;
+        self.count += size;
+        while self.count >= 8 {
+            self.bytes.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.bits as u8);
+        }
+        self.bytes
+    }
+}
+
+// GIF flavour of LZW. The alphabet only has 4 symbols, so the dictionary
+// is a table of children per code instead of a hash map.
+fn lzw_encode(mut pixels: impl Iterator<Item = u8>) -> Vec<u8> {
+    const SYMBOLS: usize = 1 << LZW_MIN_CODE_SIZE;
+    let clear = SYMBOLS as u16;
+    let end = clear + 1;
+
+    let mut children = vec![[0u16; SYMBOLS]; MAX_LZW_CODE as usize + 1];
+    let mut next = end + 1;
+    let mut size = LZW_MIN_CODE_SIZE as u32 + 1;
+    let mut out = BitWriter { bytes: Vec::new(), bits: 0, count: 0 };
+    out.write(clear, size);
+
+    let Some(first) = pixels.next() else {
+        out.write(end, size);
+        return out.finish();
+    };
+    let mut prefix = first as u16;
+
+    for pixel in pixels {
+        let child = children[prefix as usize][pixel as usize];
+        if child != 0 {
+            prefix = child;
+            continue;
+        }
+
+        out.write(prefix, size);
+        children[prefix as usize][pixel as usize] = next;
+        next += 1;
+        // the decoder adds each entry one code later than we do
+        if next > 1 << size && size < 12 {
+            size += 1;
+        }
+        if next > MAX_LZW_CODE {
+            out.write(clear, size);
+            children.iter_mut().for_each(|c| *c = [0; SYMBOLS]);
+            next = end + 1;
+            size = LZW_MIN_CODE_SIZE as u32 + 1;
+        }
+        prefix = pixel as u16;
+    }
+
+    out.write(prefix, size);
+    // the decoder adds an entry for the last code too
+    if next == 1 << size && size < 12 {
+        size += 1;
+    }
+    out.write(end, size);
+    out.finish()
+}
+
+/// Writes every frame as raw RGB24, for `ffmpeg -f rawvideo`.
+pub struct RawVideoRecorder<W: Write> {
+    out: W,
+    scale: usize,
+    colors: [[u8; 3]; 2],
+}
+
+impl<W: Write> RawVideoRecorder<W> {
+    pub fn new(out: W, scale: usize, background: [u8; 3], foreground: [u8; 3]) -> Self {
+        Self { out, scale: scale.max(1), colors: [background, foreground] }
+    }
+
+    pub fn frame<D: Display, K: Keypad, B: Buzzer, R: Rng>(&mut self, emu: &Emu<D, K, B, R>) -> io::Result<()> {
+        let mut line = Vec::with_capacity(SCREEN_WIDTH * self.scale * 3);
+        for y in 0..SCREEN_HEIGHT {
+            line.clear();
+            for x in 0..SCREEN_WIDTH {
+                let color = self.colors[emu.get_pixel(x, y) as usize];
+                for _ in 0..self.scale {
+                    line.extend_from_slice(&color);
+                }
+            }
+            for _ in 0..self.scale {
+                self.out.write_all(&line)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// The beep as a sine wave, for frontends that mix their own audio. The phase
+/// carries over between calls, so consecutive beeps don't click.
+#[derive(Default)]
+pub struct BeepSynth {
+    phase: f32,
+}
+
+impl BeepSynth {
+    /// Fills `samples` with the beep as `emu` plays it now, silence while the
+    /// sound timer is off.
+    pub fn fill<D: Display, K: Keypad, B: Buzzer, R: Rng>(&mut self, emu: &Emu<D, K, B, R>, sample_rate: u32, samples: &mut [i16]) {
+        if !emu.is_beeping() {
+            samples.fill(0);
+            return;
+        }
+        let (pitch, volume) = emu.get_beep();
+        let step = TAU * pitch / sample_rate as f32;
+        for sample in samples {
+            *sample = (self.phase.sin() * volume * i16::MAX as f32) as i16;
+            self.phase = (self.phase + step) % TAU;
+        }
+    }
+}
+
+/// Writes the beep as 16-bit mono PCM, one frame's worth of samples per
+/// `frame` call, silent while the sound timer is off.
+pub struct WavRecorder<W: Write + Seek> {
+    out: W,
+    samples: u32,
+    synth: BeepSynth,
+}
+
+impl<W: Write + Seek> WavRecorder<W> {
+    pub fn new(mut out: W) -> io::Result<Self> {
+        // the sizes are filled in by `finish`
+        out.write_all(b"RIFF\0\0\0\0WAVEfmt ")?;
+        out.write_all(&16u32.to_le_bytes())?;
+        out.write_all(&1u16.to_le_bytes())?; // PCM
+        out.write_all(&1u16.to_le_bytes())?; // mono
+        out.write_all(&AUDIO_SAMPLE_RATE.to_le_bytes())?;
+        out.write_all(&(AUDIO_SAMPLE_RATE * 2).to_le_bytes())?; // bytes per second
+        out.write_all(&2u16.to_le_bytes())?; // bytes per sample
+        out.write_all(&16u16.to_le_bytes())?; // bits per sample
+        out.write_all(b"data\0\0\0\0")?;
+        Ok(Self { out, samples: 0, synth: BeepSynth::default() })
+    }
+
+    pub fn frame<D: Display, K: Keypad, B: Buzzer, R: Rng>(&mut self, emu: &Emu<D, K, B, R>) -> io::Result<()> {
+        let mut samples = [0; SAMPLES_PER_FRAME];
+        self.synth.fill(emu, AUDIO_SAMPLE_RATE, &mut samples);
+        let data: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        self.samples += SAMPLES_PER_FRAME as u32;
+        self.out.write_all(&data)
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        let data_size = self.samples * 2;
+        self.out.seek(SeekFrom::Start(4))?;
+        self.out.write_all(&(36 + data_size).to_le_bytes())?;
+        self.out.seek(SeekFrom::Start(40))?;
+        self.out.write_all(&data_size.to_le_bytes())?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+type FileWriter = BufWriter<File>;
+
+/// A recording to files next to each other: `<base>.gif`, and with `raw`
+/// also `<base>.rgb` and `<base>.wav`.
+pub struct Recording {
+    gif: GifRecorder<FileWriter>,
+    raw: Option<(RawVideoRecorder<FileWriter>, WavRecorder<FileWriter>)>,
+}
+
+impl Recording {
+    pub fn create(base: &Path, scale: usize, background: [u8; 3], foreground: [u8; 3], raw: bool) -> io::Result<Self> {
+        // checked before any file is created
+        gif_size(scale.max(1))?;
+        let create = |extension| File::create(base.with_added_extension(extension)).map(BufWriter::new);
+        let gif = GifRecorder::new(create("gif")?, scale, background, foreground)?;
+        let raw = match raw {
+            true => Some((RawVideoRecorder::new(create("rgb")?, scale, background, foreground), WavRecorder::new(create("wav")?)?)),
+            false => None,
+        };
+        Ok(Self { gif, raw })
+    }
+
+    pub fn frame<K: Keypad, B: Buzzer, R: Rng>(&mut self, emu: &Emu<Framebuffer, K, B, R>) -> io::Result<()> {
+        self.gif.frame(emu)?;
+        if let Some((video, audio)) = &mut self.raw {
+            video.frame(emu)?;
+            audio.frame(emu)?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        self.gif.finish()?;
+        if let Some((video, audio)) = self.raw {
+            video.finish()?;
+            audio.finish()?;
+        }
+        Ok(())
+    }
+}
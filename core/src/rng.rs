@@ -0,0 +1,52 @@
+// The built-in generator for CXNN. A small xorshift instead of the thread
+// RNG, so the core also runs where there is no OS entropy (WebAssembly,
+// microcontrollers) and runs can be replayed from a seed.
+
+use crate::Rng;
+
+/// xorshift64*. `Default` seeds it from the OS with the `rand` feature and
+/// with a fixed seed without.
+#[derive(Debug, Clone)]
+pub struct XorShiftRng {
+    state: u64,
+}
+
+// Used without the `rand` feature, or when asked for a seed of 0 (which
+// xorshift can't leave)
+const DEFAULT_SEED: u64 = 0x2545_F491_4F6C_DD1D;
+
+impl XorShiftRng {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Self { state: 0 };
+        rng.seed(seed);
+        rng
+    }
+
+    pub fn seed(&mut self, seed: u64) {
+        self.state = if seed == 0 { DEFAULT_SEED } else { seed };
+    }
+
+    /// The whole generator, for save states. `seed` restores it.
+    pub(crate) fn state(&self) -> u64 {
+        self.state
+    }
+}
+
+impl Default for XorShiftRng {
+    fn default() -> Self {
+        #[cfg(feature = "rand")]
+        return Self::new(rand::random());
+        #[cfg(not(feature = "rand"))]
+        return Self::new(DEFAULT_SEED);
+    }
+}
+
+impl Rng for XorShiftRng {
+    // the high byte is the best distributed
+    fn next_u8(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+}
@@ -0,0 +1,144 @@
+// Save states: the machine as a fixed-size byte array, so frontends can
+// allocate it up front (libretro asks for the size once). Settings such as
+// quirks, timing and the beep belong to the frontend and aren't included,
+// and neither are the keys, which the frontend sets every frame.
+
+use core::fmt;
+
+use crate::{Buzzer, Emu, Framebuffer, GameTracker, Keypad, XorShiftRng, NUM_REGS, RAM_SIZE, SCREEN_HEIGHT, STACK_SIZE};
+
+const MAGIC: &[u8; 4] = b"C8S\x01"; // the last byte is the format version
+
+/// Size of every save state, in bytes.
+pub const STATE_SIZE: usize = MAGIC.len()
+    + 2 // pc
+    + RAM_SIZE
+    + SCREEN_HEIGHT * 8
+    + NUM_REGS
+    + 2 // I
+    + 1 + STACK_SIZE * 2 // depth, then every slot
+    + 2 // delay and sound timers
+    + 4 // cycle budget
+    + 1 // vblank
+    + 8 // instruction count
+    + 8; // random generator
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateError {
+    /// Not `STATE_SIZE` bytes, or not a save state at all.
+    Invalid,
+    /// Written by a version of the emulator with another format.
+    Version(u8),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::Invalid => write!(f, "not a save state"),
+            StateError::Version(version) => write!(f, "save state format {version} isn't supported"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for StateError {}
+
+impl<K: Keypad, B: Buzzer> Emu<Framebuffer, K, B, XorShiftRng> {
+    pub fn save_state(&self) -> [u8; STATE_SIZE] {
+        let mut state = [0; STATE_SIZE];
+        let mut writer = Writer(&mut state);
+        writer.put(MAGIC);
+        writer.put(&self.pc.to_le_bytes());
+        writer.put(&self.ram);
+        for row in self.screen.rows() {
+            writer.put(&row.to_le_bytes());
+        }
+        writer.put(&self.v_reg);
+        writer.put(&self.i_reg.to_le_bytes());
+        writer.put(&[self.sp as u8]);
+        for addr in self.stack {
+            writer.put(&addr.to_le_bytes());
+        }
+        writer.put(&[self.dt, self.st]);
+        writer.put(&self.cycle_budget.to_le_bytes());
+        writer.put(&[self.vblank as u8]);
+        writer.put(&self.instruction_count.to_le_bytes());
+        writer.put(&self.rng.state().to_le_bytes());
+        debug_assert!(writer.0.is_empty());
+        state
+    }
+
+    /// Restores a state from `save_state`. On error the machine is left
+    /// untouched.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        if state.len() != STATE_SIZE || state[..3] != MAGIC[..3] {
+            return Err(StateError::Invalid);
+        }
+        if state[3] != MAGIC[3] {
+            return Err(StateError::Version(state[3]));
+        }
+
+        let mut reader = Reader(&state[MAGIC.len()..]);
+        let pc = u16::from_le_bytes(reader.take());
+        let ram: [u8; RAM_SIZE] = reader.take();
+        let rows = core::array::from_fn(|_| u64::from_le_bytes(reader.take()));
+        let v_reg = reader.take();
+        let i_reg = u16::from_le_bytes(reader.take());
+        let [sp] = reader.take();
+        let stack = core::array::from_fn(|_| u16::from_le_bytes(reader.take()));
+        let [dt, st] = reader.take();
+        let cycle_budget = i32::from_le_bytes(reader.take());
+        let [vblank] = reader.take();
+        let instruction_count = u64::from_le_bytes(reader.take());
+        let rng = u64::from_le_bytes(reader.take());
+
+        // the instruction at PC has to fit in memory
+        if sp as usize > STACK_SIZE || pc as usize + 1 >= RAM_SIZE || vblank > 1 {
+            return Err(StateError::Invalid);
+        }
+
+        self.pc = pc;
+        self.ram = ram;
+        self.screen = Framebuffer::from_rows(rows);
+        self.v_reg = v_reg;
+        self.i_reg = i_reg;
+        self.stack = stack;
+        self.sp = sp as usize;
+        self.dt = dt;
+        self.st = st;
+        self.cycle_budget = cycle_budget;
+        self.vblank = vblank != 0;
+        self.instruction_count = instruction_count;
+        self.rng.seed(rng);
+        // the game is back where the state was saved, watch it from there
+        self.game = GameTracker::new(self.game.rules);
+        self.update_game_status();
+
+        // the next timer tick starts the beep again if the sound timer runs
+        self.buzzer.stop();
+        self.is_beeping = false;
+        Ok(())
+    }
+}
+
+struct Writer<'a>(&'a mut [u8]);
+
+impl Writer<'_> {
+    fn put(&mut self, field: &[u8]) {
+        let (head, rest) = core::mem::take(&mut self.0).split_at_mut(field.len());
+        head.copy_from_slice(field);
+        self.0 = rest;
+    }
+}
+
+// Reads fixed-size fields in order. The size is checked up front, so running
+// out is a bug in the layout above.
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> [u8; N] {
+        let (field, rest) = self.0.split_first_chunk().expect("save state layout");
+        self.0 = rest;
+        *field
+    }
+}
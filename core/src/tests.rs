@@ -0,0 +1,1116 @@
+// Property tests for the instruction set.
+//
+// Every test builds a random machine state and set of quirks, runs a short
+// program on the emulator and on `Spec`, a reference interpreter written
+// from the CHIP-8 documentation (not from `Emu::execute`), and checks that
+// both end up in the same state.
+
+use super::*;
+use proptest::prelude::*;
+
+const DATA: usize = 0x300; // random bytes for sprites, FX65, etc.
+const DATA_SIZE: usize = 0x100;
+const VF: usize = NUM_REGS - 1;
+
+#[derive(Debug, Clone)]
+struct State {
+    v: [u8; 16],
+    i: u16,
+    keys: [bool; 16],
+    data: Vec<u8>,
+    quirks: Quirks,
+}
+
+impl State {
+    /// Zeroed registers and data, no keys held and the default quirks.
+    fn blank() -> Self {
+        State { v: [0; 16], i: 0, keys: [false; 16], data: vec![0; DATA_SIZE], quirks: Quirks::default() }
+    }
+}
+
+/// Reference interpreter, deliberately independent of `Emu`.
+#[derive(Debug, Clone, PartialEq)]
+struct Spec {
+    pc: u16,
+    v: [u8; 16],
+    i: u16,
+    ram: Vec<u8>,
+    screen: Vec<bool>,
+    stack: Vec<u16>,
+    keys: [bool; 16],
+    dt: u8,
+    st: u8,
+    quirks: Quirks,
+}
+
+impl Spec {
+    fn step(&mut self) {
+        let pc = self.pc as usize;
+        let op = (self.ram[pc] as u16) << 8 | self.ram[pc + 1] as u16;
+        self.pc += 2;
+
+        let x = ((op >> 8) & 0xF) as usize;
+        let y = ((op >> 4) & 0xF) as usize;
+        let n = (op & 0xF) as u8;
+        let nn = (op & 0xFF) as u8;
+        let nnn = op & 0xFFF;
+
+        match op >> 12 {
+            0x0 => match op {
+                0x0000 => {}
+                0x00E0 => self.screen.iter_mut().for_each(|p| *p = false),
+                0x00EE => self.pc = self.stack.pop().unwrap(),
+                _ => panic!("spec: bad opcode {op:#06x}"),
+            },
+            0x1 => self.pc = nnn,
+            0x2 => {
+                self.stack.push(self.pc);
+                self.pc = nnn;
+            }
+            0x3 => self.skip_if(self.v[x] == nn),
+            0x4 => self.skip_if(self.v[x] != nn),
+            0x5 => self.skip_if(self.v[x] == self.v[y]),
+            0x6 => self.v[x] = nn,
+            0x7 => self.v[x] = ((self.v[x] as u16 + nn as u16) % 256) as u8, // no carry flag
+            0x8 => {
+                let (vx, vy) = (self.v[x] as u16, self.v[y] as u16);
+                let logic_flag = if self.quirks.vf_reset { Some(0) } else { None };
+                let shifted = if self.quirks.shift_uses_vy { vy } else { vx };
+                // (result, flag); the flag is written after the result
+                let (result, flag) = match n {
+                    0x0 => (vy, None),
+                    0x1 => (vx | vy, logic_flag),
+                    0x2 => (vx & vy, logic_flag),
+                    0x3 => (vx ^ vy, logic_flag),
+                    0x4 => (vx + vy, Some((vx + vy > 0xFF) as u8)),
+                    0x5 => (vx + 0x100 - vy, Some((vx >= vy) as u8)),
+                    0x6 => (shifted / 2, Some((shifted % 2) as u8)),
+                    0x7 => (vy + 0x100 - vx, Some((vy >= vx) as u8)),
+                    0xE => (shifted * 2, Some((shifted / 0x80) as u8)),
+                    _ => panic!("spec: bad opcode {op:#06x}"),
+                };
+                self.v[x] = (result % 0x100) as u8;
+                if let Some(flag) = flag {
+                    self.v[VF] = flag;
+                }
+            }
+            0x9 => self.skip_if(self.v[x] != self.v[y]),
+            0xA => self.i = nnn,
+            0xB if self.quirks.jump_uses_vx => self.pc = nnn + self.v[x] as u16,
+            0xB => self.pc = nnn + self.v[0] as u16,
+            0xC => unreachable!("CXNN is random and checked separately"),
+            0xD => self.draw(self.v[x] as usize, self.v[y] as usize, n as usize),
+            0xE => match nn {
+                0x9E => self.skip_if(self.keys[self.v[x] as usize]),
+                0xA1 => self.skip_if(!self.keys[self.v[x] as usize]),
+                _ => panic!("spec: bad opcode {op:#06x}"),
+            },
+            0xF => match nn {
+                0x07 => self.v[x] = self.dt,
+                0x0A => match self.keys.iter().position(|&k| k) {
+                    Some(key) => self.v[x] = key as u8,
+                    None => self.pc -= 2, // block on this instruction
+                },
+                0x15 => self.dt = self.v[x],
+                0x18 => self.st = self.v[x],
+                0x1E => self.i += self.v[x] as u16,
+                0x29 => self.i = self.v[x] as u16 * 5,
+                0x33 => {
+                    let i = self.i as usize;
+                    self.ram[i] = self.v[x] / 100;
+                    self.ram[i + 1] = self.v[x] / 10 % 10;
+                    self.ram[i + 2] = self.v[x] % 10;
+                }
+                0x55 => {
+                    for r in 0..=x {
+                        self.ram[self.i as usize + r] = self.v[r];
+                    }
+                    if self.quirks.memory_increment {
+                        self.i += if self.quirks.memory_increment_by_x { x as u16 } else { x as u16 + 1 };
+                    }
+                }
+                0x65 => {
+                    for r in 0..=x {
+                        self.v[r] = self.ram[self.i as usize + r];
+                    }
+                    if self.quirks.memory_increment {
+                        self.i += if self.quirks.memory_increment_by_x { x as u16 } else { x as u16 + 1 };
+                    }
+                }
+                _ => panic!("spec: bad opcode {op:#06x}"),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    fn skip_if(&mut self, cond: bool) {
+        if cond {
+            self.pc += 2;
+        }
+    }
+
+    // Sprites are 8 pixels wide. The starting position wraps around the
+    // display; the pixels past its edges wrap too, or are clipped.
+    fn draw(&mut self, x0: usize, y0: usize, rows: usize) {
+        let (x0, y0) = (x0 % SCREEN_WIDTH, y0 % SCREEN_HEIGHT);
+        let clip = self.quirks.clip_sprites;
+        let mut collision = false;
+        for row in 0..rows {
+            let bits = self.ram[self.i as usize + row];
+            for col in 0..8 {
+                if bits & (0x80 >> col) == 0 {
+                    continue;
+                }
+                if clip && (x0 + col >= SCREEN_WIDTH || y0 + row >= SCREEN_HEIGHT) {
+                    continue;
+                }
+                let px = (x0 + col) % SCREEN_WIDTH;
+                let py = (y0 + row) % SCREEN_HEIGHT;
+                let pixel = &mut self.screen[py * SCREEN_WIDTH + px];
+                collision |= *pixel;
+                *pixel = !*pixel;
+            }
+        }
+        self.v[VF] = collision as u8;
+    }
+
+    fn from_emu(emu: &Emu) -> Self {
+        Spec {
+            pc: emu.pc,
+            v: emu.v_reg,
+            i: emu.i_reg,
+            ram: emu.ram.to_vec(),
+            screen: (0..SCREEN_HEIGHT)
+                .flat_map(|y| (0..SCREEN_WIDTH).map(move |x| (x, y)))
+                .map(|(x, y)| emu.get_pixel(x, y))
+                .collect(),
+            stack: emu.stack[..emu.sp].to_vec(),
+            keys: std::array::from_fn(|key| emu.keys.is_pressed(key)),
+            dt: emu.dt,
+            st: emu.st,
+            quirks: emu.quirks,
+        }
+    }
+}
+
+/// Builds an emulator in `state` with `program` at the start address.
+fn setup(state: &State, program: &[u16]) -> Emu {
+    let mut emu = Emu::new();
+    let code: Vec<u8> = program.iter().flat_map(|op| op.to_be_bytes()).collect();
+    emu.load_rom(&code);
+    emu.ram[DATA..DATA + DATA_SIZE].copy_from_slice(&state.data);
+    emu.v_reg = state.v;
+    emu.i_reg = state.i;
+    for (key, &pressed) in state.keys.iter().enumerate() {
+        emu.keypress(key, pressed);
+    }
+    emu.quirks = state.quirks;
+    emu
+}
+
+/// Runs `program` on both the emulator and the spec, returning both.
+fn run(state: &State, program: &[u16]) -> (Emu, Spec) {
+    let mut emu = setup(state, program);
+    let mut spec = Spec::from_emu(&emu);
+    for _ in 0..program.len() {
+        emu.tick();
+        spec.step();
+    }
+    (emu, spec)
+}
+
+fn check(state: &State, program: &[u16]) -> Result<(), TestCaseError> {
+    let (emu, spec) = run(state, program);
+    prop_assert_eq!(Spec::from_emu(&emu), spec);
+    Ok(())
+}
+
+fn state() -> impl Strategy<Value = State> {
+    (
+        any::<[u8; 16]>(),
+        DATA as u16..(DATA + DATA_SIZE - 16) as u16,
+        any::<[bool; 16]>(),
+        prop::collection::vec(any::<u8>(), DATA_SIZE),
+        any::<[bool; 6]>(),
+    )
+        .prop_map(|(v, i, keys, data, q)| {
+            // every combination of quirks
+            let quirks = Quirks {
+                vf_reset: q[0],
+                memory_increment: q[1],
+                memory_increment_by_x: q[5],
+                shift_uses_vy: q[2],
+                jump_uses_vx: q[3],
+                clip_sprites: q[4],
+            };
+            State { v, i, keys, data, quirks }
+        })
+}
+
+/// Like `state`, but VX holds a valid key index for EX9E/EXA1.
+fn key_state(x: u8) -> impl Strategy<Value = State> {
+    (state(), 0..16u8).prop_map(move |(mut s, key)| {
+        s.v[x as usize] = key;
+        s
+    })
+}
+
+fn reg() -> impl Strategy<Value = u16> {
+    0..16u16
+}
+
+proptest! {
+    #[test]
+    fn nop(s in state()) {
+        check(&s, &[0x0000])?;
+    }
+
+    #[test]
+    fn clear_screen(s in state(), x in reg(), y in reg(), n in 1..16u16) {
+        check(&s, &[0xD000 | x << 8 | y << 4 | n, 0x00E0])?;
+    }
+
+    #[test]
+    fn call_and_ret(s in state()) {
+        // CALL 0x204 from 0x200, RET lands on the NOP at 0x202
+        check(&s, &[0x2204, 0x0000, 0x00EE])?;
+    }
+
+    #[test]
+    fn jump(s in state(), nnn in 0..0x1000u16) {
+        check(&s, &[0x1000 | nnn])?;
+    }
+
+    #[test]
+    fn call(s in state(), nnn in 0..0x1000u16) {
+        check(&s, &[0x2000 | nnn])?;
+    }
+
+    #[test]
+    fn skip_eq(s in state(), x in reg(), nn in any::<u8>(), hit in any::<bool>()) {
+        let nn = if hit { s.v[x as usize] } else { nn };
+        check(&s, &[0x3000 | x << 8 | nn as u16])?;
+    }
+
+    #[test]
+    fn skip_neq(s in state(), x in reg(), nn in any::<u8>(), hit in any::<bool>()) {
+        let nn = if hit { s.v[x as usize] } else { nn };
+        check(&s, &[0x4000 | x << 8 | nn as u16])?;
+    }
+
+    #[test]
+    fn skip_eq_reg(s in state(), x in reg(), y in reg()) {
+        check(&s, &[0x5000 | x << 8 | y << 4])?;
+    }
+
+    #[test]
+    fn set_reg(s in state(), x in reg(), nn in any::<u8>()) {
+        check(&s, &[0x6000 | x << 8 | nn as u16])?;
+    }
+
+    #[test]
+    fn add_reg(s in state(), x in reg(), nn in any::<u8>()) {
+        check(&s, &[0x7000 | x << 8 | nn as u16])?;
+    }
+
+    #[test]
+    fn alu(s in state(), x in reg(), y in reg(), n in prop::sample::select(vec![0, 1, 2, 3, 4, 5, 6, 7, 0xE])) {
+        check(&s, &[0x8000 | x << 8 | y << 4 | n])?;
+    }
+
+    #[test]
+    fn skip_neq_reg(s in state(), x in reg(), y in reg()) {
+        check(&s, &[0x9000 | x << 8 | y << 4])?;
+    }
+
+    #[test]
+    fn set_i_reg(s in state(), nnn in 0..0x1000u16) {
+        check(&s, &[0xA000 | nnn])?;
+    }
+
+    #[test]
+    fn jump_offset(s in state(), nnn in 0..0x1000u16) {
+        check(&s, &[0xB000 | nnn])?;
+    }
+
+    #[test]
+    fn rand_is_masked(s in state(), x in reg(), nn in any::<u8>()) {
+        let mut emu = setup(&s, &[0xC000 | x << 8 | nn as u16]);
+        emu.tick();
+        let mut expected = s.v;
+        let vx = emu.v_reg[x as usize];
+        prop_assert_eq!(vx & !nn, 0);
+        expected[x as usize] = vx;
+        prop_assert_eq!(emu.v_reg, expected);
+        prop_assert_eq!(emu.pc, START_ADDR + 2);
+    }
+
+    #[test]
+    fn draw(s in state(), x in reg(), y in reg(), n in 0..16u16) {
+        check(&s, &[0xD000 | x << 8 | y << 4 | n])?;
+    }
+
+    #[test]
+    fn draw_collision(s in state(), x1 in reg(), y1 in reg(), n1 in 0..16u16, x2 in reg(), y2 in reg(), n2 in 0..16u16) {
+        check(&s, &[0xD000 | x1 << 8 | y1 << 4 | n1, 0xD000 | x2 << 8 | y2 << 4 | n2])?;
+    }
+
+    #[test]
+    fn draw_twice_restores_screen(s in state(), x in 0..15u16, y in 0..15u16, n in 1..16u16) {
+        let mut s = s;
+        s.quirks.clip_sprites = false; // clipped pixels would not count as collisions
+        // VF changes between the draws, so keep it out of the coordinates
+        let (emu, _) = run(&s, &[0xD000 | x << 8 | y << 4 | n, 0xD000 | x << 8 | y << 4 | n]);
+        prop_assert!(emu.get_screen().iter().all(|&row| row == 0));
+        let sprite = &s.data[s.i as usize - DATA..][..n as usize];
+        prop_assert_eq!(emu.v_reg[VF], sprite.iter().any(|&b| b != 0) as u8);
+    }
+
+    #[test]
+    fn skip_key((x, s) in reg().prop_flat_map(|x| (Just(x), key_state(x as u8)))) {
+        check(&s, &[0xE09E | x << 8])?;
+    }
+
+    #[test]
+    fn skip_not_key((x, s) in reg().prop_flat_map(|x| (Just(x), key_state(x as u8)))) {
+        check(&s, &[0xE0A1 | x << 8])?;
+    }
+
+    #[test]
+    fn delay_timer(s in state(), x in reg(), y in reg()) {
+        check(&s, &[0xF015 | x << 8, 0xF007 | y << 8])?;
+    }
+
+    #[test]
+    fn wait_key(s in state(), x in reg(), none in any::<bool>()) {
+        let mut s = s;
+        if none {
+            s.keys = [false; 16];
+        }
+        check(&s, &[0xF00A | x << 8])?;
+    }
+
+    #[test]
+    fn sound_timer(s in state(), x in reg()) {
+        check(&s, &[0xF018 | x << 8])?;
+    }
+
+    #[test]
+    fn add_i_reg(s in state(), x in reg()) {
+        check(&s, &[0xF01E | x << 8])?;
+    }
+
+    #[test]
+    fn font_sprite(s in state(), x in reg()) {
+        check(&s, &[0xF029 | x << 8])?;
+    }
+
+    #[test]
+    fn store_bcd(s in state(), x in reg()) {
+        check(&s, &[0xF033 | x << 8])?;
+    }
+
+    #[test]
+    fn store_regs(s in state(), x in reg()) {
+        check(&s, &[0xF055 | x << 8])?;
+    }
+
+    #[test]
+    fn load_regs(s in state(), x in reg()) {
+        check(&s, &[0xF065 | x << 8])?;
+    }
+}
+
+// COSMAC VIP timing
+
+#[test]
+fn vip_timing_draws_once_per_frame() {
+    // 0x200: DXYN, 0x202: JP 0x200
+    let mut emu = setup(&State::blank(), &[0xD005, 0x1200]);
+    emu.set_timing(Timing::CosmacVip);
+
+    for frame in 0..4 {
+        emu.run_frame(0);
+        // the "0" font sprite is drawn once, then the next DXYN waits for vblank
+        assert_eq!(emu.pc, 0x200);
+        assert_eq!(emu.get_pixel(0, 0), frame % 2 == 0);
+    }
+}
+
+#[test]
+fn vip_timing_spends_the_cycle_budget() {
+    // 0x200: ADD V0, 1; 0x202: JP 0x200
+    let mut emu = setup(&State::blank(), &[0x7001, 0x1200]);
+    emu.set_timing(Timing::CosmacVip);
+    emu.run_frame(1000);
+
+    let add = timing::vip_cycles(Decoded::AddReg(0, 1), &[0; 16], false);
+    let jump = timing::vip_cycles(Decoded::Jump(0x200), &[0; 16], false);
+    let iterations = (timing::VIP_CYCLES_FOR_PROGRAM + add + jump - 1) / (add + jump);
+    assert!((emu.v_reg[0] as i32 - iterations).abs() <= 1, "{} adds, expected ~{}", emu.v_reg[0], iterations);
+    assert_eq!(emu.dt, 0);
+}
+
+// Framebuffer
+
+#[test]
+fn dirty_rect_covers_changes() {
+    let mut fb = Framebuffer::new();
+    assert!(fb.take_dirty().is_some()); // a new screen must be drawn once
+    assert_eq!(fb.take_dirty(), None);
+
+    // wraps around the right edge: pixels 62, 63, 0 and 1
+    assert!(!fb.draw_sprite_row(62, 5, 0xF0, false));
+    assert_eq!(fb.take_dirty(), Some(DirtyRect { x: 0, y: 5, width: 64, height: 1 }));
+
+    assert!(!fb.draw_sprite_row(8, 10, 0x81, false));
+    assert!(!fb.draw_sprite_row(8, 12, 0x80, false));
+    assert_eq!(fb.take_dirty(), Some(DirtyRect { x: 8, y: 10, width: 8, height: 3 }));
+
+    assert!(fb.draw_sprite_row(8, 10, 0x01, false));
+    assert!(!fb.pixel(15, 10) && fb.pixel(8, 10));
+    fb.clear();
+    assert!(fb.rows().iter().all(|&row| row == 0));
+    assert_eq!(fb.take_dirty(), Some(DirtyRect { x: 0, y: 5, width: 64, height: 8 }));
+
+    // clipped: only pixels 62 and 63
+    fb.draw_sprite_row(62, 0, 0xF0, true);
+    assert_eq!(fb.rows()[0], 0b11);
+}
+
+#[test]
+fn framebuffer_writes_a_byte_per_pixel() {
+    let mut fb = Framebuffer::new();
+    fb.draw_sprite_row(0, 0, 0x81, false);
+    fb.draw_sprite_row(60, 31, 0x90, false);
+    let mut bytes = [0xFF; SCREEN_WIDTH * SCREEN_HEIGHT + 1];
+    fb.write_bytes(&mut bytes);
+
+    let lit: Vec<usize> = (0..bytes.len()).filter(|&i| bytes[i] == 1).collect();
+    assert_eq!(lit, [0, 7, 31 * SCREEN_WIDTH + 60, 31 * SCREEN_WIDTH + 63]);
+    assert!(bytes[..SCREEN_WIDTH * SCREEN_HEIGHT].iter().all(|&b| b <= 1));
+    assert_eq!(bytes[SCREEN_WIDTH * SCREEN_HEIGHT], 0xFF); // past the screen
+}
+
+#[test]
+fn instruction_count_follows_the_timing_model() {
+    // 0x200: ADD V0, 1; 0x202: JP 0x200
+    let mut emu = setup(&State::blank(), &[0x7001, 0x1200]);
+    emu.run_frame(10);
+    emu.run_frame(10);
+    assert_eq!(emu.get_instruction_count(), 20);
+
+    emu.set_timing(Timing::CosmacVip);
+    emu.run_frame(10);
+    // every ADD is followed by a JP, the frame may end between the two
+    let adds = emu.v_reg[0] as u64 - 10;
+    assert!((2 * adds - 1..=2 * adds).contains(&(emu.get_instruction_count() - 20)));
+
+    emu.reset();
+    assert_eq!(emu.get_instruction_count(), 0);
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn screenshot_is_a_valid_png() {
+    // "0" font sprite in the top-left corner
+    let mut emu = setup(&State::blank(), &[0xD005]);
+    emu.tick();
+    let png = emu.screenshot_png(2, [0, 0, 0], [0xFF, 0xFF, 0xFF]);
+
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    assert_eq!(&png[12..16], b"IHDR");
+    assert_eq!(&png[16..24], &[0, 0, 0, 128, 0, 0, 0, 64]);
+    // the IEND chunk, CRC included, is always the same
+    assert_eq!(&png[png.len() - 12..], b"\0\0\0\0IEND\xae\x42\x60\x82");
+
+    // first row of pixels: filter byte, then "0"'s top row (1111) doubled
+    let idat = png.windows(4).position(|w| w == b"IDAT").unwrap() + 4;
+    let raw = &png[idat + 2 + 5..]; // zlib header, stored block header
+    assert_eq!(&raw[..3], &[0, 0xFF, 0]);
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn recorders_follow_the_emulated_time() {
+    use std::io::Cursor;
+
+    // 0x200: LD V0, 30; 0x202: LD ST, V0; 0x204: JP 0x204
+    let mut emu = setup(&State::blank(), &[0x601E, 0xF018, 0x1204]);
+    let mut gif = GifRecorder::new(Vec::new(), 1, [0; 3], [0xFF; 3]).unwrap();
+    let mut wav = WavRecorder::new(Cursor::new(Vec::new())).unwrap();
+    for _ in 0..60 {
+        emu.run_frame(10);
+        gif.frame(&emu).unwrap();
+        wav.frame(&emu).unwrap();
+    }
+
+    // the screen never changes: a single frame lasting one second
+    let gif = gif.finish().unwrap();
+    assert_eq!(&gif[..6], b"GIF89a");
+    assert_eq!(gif.iter().filter(|&&b| b == 0x2C).count(), 1);
+    let control = gif.windows(3).position(|w| w == [0x21, 0xF9, 0x04]).unwrap();
+    assert_eq!(&gif[control + 4..control + 6], &100u16.to_le_bytes());
+    assert_eq!(gif.last(), Some(&0x3B));
+
+    // one second of audio, the first half second beeping
+    let wav = wav.finish().unwrap().into_inner();
+    let samples: Vec<i16> = wav[44..].chunks(2).map(|s| i16::from_le_bytes([s[0], s[1]])).collect();
+    assert_eq!(samples.len(), AUDIO_SAMPLE_RATE as usize);
+    assert_eq!(&wav[40..44], &(AUDIO_SAMPLE_RATE * 2).to_le_bytes());
+    let half = samples.len() / 2;
+    assert!(samples[..half].iter().any(|&s| s != 0));
+    assert!(samples[half..].iter().all(|&s| s == 0));
+}
+
+// Reads back what GifRecorder writes: each image's delay and pixels, and
+// how many times the LZW dictionary was cleared
+#[cfg(feature = "std")]
+fn decode_gif(gif: &[u8]) -> (Vec<(u16, Vec<u8>)>, usize) {
+    let mut images = Vec::new();
+    let mut clears = 0;
+    let mut delay = 0;
+    let mut pos = 6 + 7 + 2 * 3; // header, screen descriptor, 2 colours
+    let sub_blocks = |pos: &mut usize| {
+        let mut data = Vec::new();
+        while gif[*pos] != 0 {
+            let len = gif[*pos] as usize;
+            data.extend_from_slice(&gif[*pos + 1..*pos + 1 + len]);
+            *pos += len + 1;
+        }
+        *pos += 1;
+        data
+    };
+    loop {
+        match gif[pos] {
+            0x21 => {
+                if gif[pos + 1] == 0xF9 {
+                    delay = u16::from_le_bytes([gif[pos + 4], gif[pos + 5]]);
+                }
+                pos += 2;
+                sub_blocks(&mut pos);
+            },
+            0x2C => {
+                let width = u16::from_le_bytes([gif[pos + 5], gif[pos + 6]]) as usize;
+                let height = u16::from_le_bytes([gif[pos + 7], gif[pos + 8]]) as usize;
+                let min_size = gif[pos + 10];
+                pos += 11;
+                let pixels = lzw_decode(&sub_blocks(&mut pos), min_size, &mut clears);
+                assert_eq!(pixels.len(), width * height);
+                images.push((delay, pixels));
+            },
+            0x3B => return (images, clears),
+            other => panic!("unexpected block {other:#X} at {pos}"),
+        }
+    }
+}
+
+// Textbook GIF LZW decoder, independent of the encoder in record.rs
+#[cfg(feature = "std")]
+fn lzw_decode(data: &[u8], min_size: u8, clears: &mut usize) -> Vec<u8> {
+    let clear = 1usize << min_size;
+    let reset = || (0..clear + 2).map(|symbol| vec![symbol as u8]).collect::<Vec<_>>();
+    let mut table = reset();
+    let mut size = min_size as usize + 1;
+    let mut prev: Option<Vec<u8>> = None;
+    let mut out = Vec::new();
+    let mut bit = 0;
+    loop {
+        let code = (0..size).fold(0, |code, i| code | ((data[(bit + i) / 8] as usize >> ((bit + i) % 8)) & 1) << i);
+        bit += size;
+        if code == clear {
+            *clears += 1;
+            table = reset();
+            size = min_size as usize + 1;
+            prev = None;
+            continue;
+        }
+        if code == clear + 1 {
+            return out;
+        }
+        let entry = match (table.get(code), &prev) {
+            (Some(entry), _) => entry.clone(),
+            // the code being defined: the previous string plus its first symbol
+            (None, Some(prev)) if code == table.len() => [prev.as_slice(), &prev[..1]].concat(),
+            _ => panic!("code {code} isn't in the table"),
+        };
+        if let Some(prev) = prev {
+            if table.len() < 4096 {
+                table.push([prev.as_slice(), &entry[..1]].concat());
+            }
+            if table.len() == 1 << size && size < 12 {
+                size += 1;
+            }
+        }
+        out.extend_from_slice(&entry);
+        prev = Some(entry);
+    }
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn gif_frames_decode_to_the_screen() {
+    let scale = 8;
+    let mut emu = setup(&State::blank(), &[0x1200]);
+    let mut gif = GifRecorder::new(Vec::new(), scale, [0; 3], [0xFF; 3]).unwrap();
+
+    // noise compresses badly, so the dictionary fills up and is cleared
+    // several times, then a blank screen makes long strings
+    let mut rng = XorShiftRng::new(7);
+    let noise: [u64; SCREEN_HEIGHT] = std::array::from_fn(|_| u64::from_be_bytes(std::array::from_fn(|_| crate::Rng::next_u8(&mut rng))));
+    let frames = [noise, [0; SCREEN_HEIGHT]];
+    for rows in frames {
+        *emu.display_mut() = Framebuffer::from_rows(rows);
+        for _ in 0..6 {
+            gif.frame(&emu).unwrap();
+        }
+    }
+
+    let (images, clears) = decode_gif(&gif.finish().unwrap());
+    assert!(clears > 2, "{clears} clears");
+    assert_eq!(images.len(), frames.len());
+    for ((delay, pixels), rows) in images.iter().zip(frames) {
+        assert_eq!(*delay, 10);
+        let expected: Vec<u8> = (0..SCREEN_HEIGHT * scale)
+            .flat_map(|y| (0..SCREEN_WIDTH * scale).map(move |x| (rows[y / scale] >> (SCREEN_WIDTH - 1 - x / scale)) as u8 & 1))
+            .collect();
+        assert!(*pixels == expected);
+    }
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn gif_limits_are_respected() {
+    // 1024 * 64 pixels doesn't fit in a GIF's 16-bit width
+    assert!(GifRecorder::new(Vec::new(), 1023, [0; 3], [0xFF; 3]).is_ok());
+    let error = GifRecorder::new(Vec::new(), 1024, [0; 3], [0xFF; 3]).err().unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    assert!(GifRecorder::new(Vec::new(), usize::MAX, [0; 3], [0xFF; 3]).is_err());
+
+    // a still screen for 40000 frames (666.67 s) is longer than one frame's
+    // 655.35 s, so the frame is repeated
+    let emu = setup(&State::blank(), &[0x1200]);
+    let mut gif = GifRecorder::new(Vec::new(), 1, [0; 3], [0xFF; 3]).unwrap();
+    for _ in 0..40000 {
+        gif.frame(&emu).unwrap();
+    }
+    let (images, _) = decode_gif(&gif.finish().unwrap());
+    let delays: Vec<u16> = images.iter().map(|(delay, _)| *delay).collect();
+    assert_eq!(delays, [u16::MAX, 1132]); // 66667 cs in all
+    assert_eq!(images[0].1, images[1].1);
+}
+
+#[test]
+fn faults_stop_before_the_instruction() {
+    // (program, V0, I, fault)
+    let cases = [
+        (vec![0x00FF], 0, 0, Fault::UnknownOpcode { pc: 0x200, opcode: 0x00FF }),
+        (vec![0x6001, 0x00EE], 0, 0, Fault::StackUnderflow { pc: 0x202 }),
+        (vec![0xE09E], 0x10, 0, Fault::InvalidKey { pc: 0x200, key: 0x10 }),
+        (vec![0xE0A1], 0xFF, 0, Fault::InvalidKey { pc: 0x200, key: 0xFF }),
+        (vec![0xD005], 0, 0xFFC, Fault::MemoryOutOfRange { pc: 0x200, i: 0xFFC }),
+        (vec![0xF033], 0, 0xFFE, Fault::MemoryOutOfRange { pc: 0x200, i: 0xFFE }),
+        (vec![0xF155], 0, 0xFFF, Fault::MemoryOutOfRange { pc: 0x200, i: 0xFFF }),
+        (vec![0xF065], 0, 0x1000, Fault::MemoryOutOfRange { pc: 0x200, i: 0x1000 }),
+        (vec![0x1FFF], 0, 0, Fault::PcOutOfRange { pc: 0xFFF }),
+    ];
+    for (program, v0, i, fault) in cases {
+        let mut state = State::blank();
+        state.v[0] = v0;
+        state.i = i;
+        let mut emu = setup(&state, &program);
+        while emu.try_tick().is_ok() {}
+
+        let before = emu.save_state();
+        assert_eq!(emu.try_tick(), Err(fault));
+        assert_eq!(emu.try_run_frame(1), Err(fault));
+        assert_eq!(emu.save_state(), before, "{fault}");
+    }
+
+    // the last byte of memory is still readable, and clipped rows aren't read
+    let mut state = State::blank();
+    state.i = 0xFFF;
+    state.v[1] = SCREEN_HEIGHT as u8 - 1;
+    state.quirks.clip_sprites = true;
+    assert_eq!(setup(&state, &[0xF065, 0xD015]).try_run_frame(2), Ok(()));
+    state.quirks.clip_sprites = false;
+    assert_eq!(setup(&state, &[0xD015]).try_tick(), Err(Fault::MemoryOutOfRange { pc: 0x200, i: 0xFFF }));
+}
+
+#[test]
+#[should_panic(expected = "unknown opcode 00FF at 0x200")]
+fn tick_panics_on_a_fault() {
+    setup(&State::blank(), &[0x00FF]).tick();
+}
+
+#[test]
+fn seeded_rand_is_reproducible() {
+    // 0x200: RND V0, 0xFF; 0x202: ADD I, V0 (so I accumulates the results)
+    let run = |seed| {
+        let mut emu = setup(&State::blank(), &[0xC0FF, 0xF01E, 0x1200]);
+        emu.set_seed(seed);
+        emu.run_frame(300);
+        (emu.i_reg, emu.v_reg[0])
+    };
+    assert_eq!(run(1), run(1));
+    assert_ne!(run(1), run(2));
+}
+
+#[test]
+fn save_state_round_trip() {
+    // 0x200: CALL 0x206; 0x202: JP 0x200; 0x206: RND V0, 0xFF; LD ST, V0;
+    // DRW V0, V1, 5; RET
+    let state = State::blank();
+    let mut emu = setup(&state, &[0x2206, 0x1200, 0x0000, 0xC0FF, 0xF018, 0xD015, 0x00EE]);
+    emu.run_frame(4); // stops inside the subroutine
+    assert_eq!(emu.sp, 1);
+
+    let saved = emu.save_state();
+    assert_eq!(saved.len(), STATE_SIZE);
+    let mut expected = Vec::new();
+    for _ in 0..10 {
+        emu.run_frame(7);
+        expected.push((emu.pc, emu.v_reg, emu.st, emu.get_screen().to_vec()));
+    }
+
+    let mut restored = setup(&state, &[]);
+    restored.load_state(&saved).unwrap();
+    assert_eq!(restored.save_state(), saved);
+    assert_eq!(restored.take_dirty(), Some(DirtyRect { x: 0, y: 0, width: SCREEN_WIDTH, height: SCREEN_HEIGHT }));
+    for frame in expected {
+        restored.run_frame(7);
+        assert_eq!((restored.pc, restored.v_reg, restored.st, restored.get_screen().to_vec()), frame);
+    }
+
+    assert_eq!(restored.load_state(&saved[1..]), Err(StateError::Invalid));
+    let mut future = saved;
+    future[3] = 0xFF;
+    assert_eq!(restored.load_state(&future), Err(StateError::Version(0xFF)));
+
+    // PC comes right after the magic: the last instruction starts at 0xFFE
+    let mut at_end = saved;
+    at_end[4..6].copy_from_slice(&0xFFFu16.to_le_bytes());
+    assert_eq!(restored.load_state(&at_end), Err(StateError::Invalid));
+    at_end[4..6].copy_from_slice(&0xFFEu16.to_le_bytes());
+    assert_eq!(restored.load_state(&at_end), Ok(()));
+    assert_eq!(restored.pc, 0xFFE);
+}
+
+#[derive(Default)]
+struct MockDisplay {
+    draws: Vec<(usize, usize, u8)>,
+    clears: usize,
+}
+
+impl Display for MockDisplay {
+    fn clear(&mut self) {
+        self.clears += 1;
+    }
+
+    fn draw_sprite_row(&mut self, x: usize, y: usize, sprite_row: u8, _clip: bool) -> bool {
+        self.draws.push((x, y, sprite_row));
+        false
+    }
+
+    fn pixel(&self, _x: usize, _y: usize) -> bool {
+        false
+    }
+}
+
+struct MockKeypad(usize); // the only key held
+
+impl Keypad for MockKeypad {
+    fn is_pressed(&self, key: usize) -> bool {
+        key == self.0
+    }
+}
+
+#[derive(Default)]
+struct MockBuzzer(Vec<&'static str>);
+
+impl Buzzer for MockBuzzer {
+    fn start(&mut self, _pitch: f32, _volume: f32) {
+        self.0.push("start");
+    }
+
+    fn stop(&mut self) {
+        self.0.push("stop");
+    }
+}
+
+#[test]
+fn peripherals_can_be_mocked() {
+    let rng: fn() -> u8 = || 0xA5;
+    let mut emu = Emu::with_peripherals(MockDisplay::default(), MockKeypad(2), MockBuzzer::default(), rng);
+    // RND V0, 0x3C; LD V1, 2; SKP V1; CLS; LD ST, V1; DRW V0, V0, 5 ("0")
+    let program: Vec<u8> = [0xC03Cu16, 0x6102, 0xE19E, 0x00E0, 0xF118, 0xD005]
+        .iter()
+        .flat_map(|op| op.to_be_bytes())
+        .collect();
+    emu.load_rom(&program);
+    for _ in 0..5 {
+        emu.tick();
+    }
+    for _ in 0..3 {
+        emu.tick_timers();
+    }
+
+    // 0xA5 & 0x3C = 0x24, so the glyph is drawn at (36, 36 % 32)
+    let glyph = [0xF0, 0x90, 0x90, 0x90, 0xF0];
+    let expected: Vec<_> = glyph.iter().enumerate().map(|(row, &bits)| (36, 4 + row, bits)).collect();
+    assert_eq!(emu.display().draws, expected);
+    assert_eq!(emu.display().clears, 0); // skipped by SKP
+    assert_eq!(emu.buzzer().0, ["start", "stop"]);
+
+    emu.reset();
+    assert_eq!(emu.display().clears, 1);
+}
+
+#[test]
+fn stack_drops_the_oldest_call_when_full() {
+    // 0x200: CALL 0x202; 0x202: CALL 0x204... four calls more than fit
+    let calls = STACK_SIZE as u16 + 4;
+    let program: Vec<u16> = (1..=calls).map(|n| 0x2200 + 2 * n).collect();
+    let mut emu = setup(&State::blank(), &program);
+    for _ in 0..calls {
+        emu.tick();
+    }
+    assert_eq!(emu.pc, 0x200 + 2 * calls);
+    assert_eq!(emu.sp, STACK_SIZE);
+    // the return addresses of the innermost calls, oldest first
+    let expected: Vec<u16> = (5..=calls).map(|n| 0x200 + 2 * n).collect();
+    assert_eq!(emu.stack.to_vec(), expected);
+
+    // RET 16 times gets back to the oldest one kept
+    emu.ram[emu.pc as usize..emu.pc as usize + 2].copy_from_slice(&[0x00, 0xEE]);
+    for _ in 0..STACK_SIZE {
+        emu.pc = 0x200 + 2 * calls;
+        emu.tick();
+    }
+    assert_eq!((emu.pc, emu.sp), (0x20A, 0));
+}
+
+// Waits for key 5, adds a point to V0 and stores it as BCD at 0x300, then
+// waits for the key to be released.
+#[cfg(feature = "std")]
+const SCORING_ROM: [u16; 9] = [0x6105, 0xE19E, 0x1202, 0x7001, 0xA300, 0xF033, 0xE1A1, 0x120C, 0x1202];
+
+#[cfg(feature = "std")]
+#[test]
+fn env_rewards_score_changes() {
+    let rom: Vec<u8> = SCORING_ROM.iter().flat_map(|op| op.to_be_bytes()).collect();
+    let mut env = Env::new(&rom, ScoreDelta::new(RamValue::new(0x300, 3, Encoding::Bcd)));
+    env.set_max_steps(Some(4));
+
+    let rewards: Vec<_> = [1 << 5, 1 << 5, 0, 1 << 5].into_iter().map(|action| env.step(action).reward).collect();
+    assert_eq!(rewards, [1.0, 0.0, 0.0, 1.0]);
+    assert_eq!(env.emu().get_registers()[0], 2);
+    assert!(!env.step(0).terminated);
+    assert!(env.step(0).truncated);
+
+    env.reset();
+    assert_eq!(env.steps(), 0);
+    assert_eq!(env.step(1 << 5).reward, 1.0);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn env_clones_are_independent() {
+    let rom: Vec<u8> = SCORING_ROM.iter().flat_map(|op| op.to_be_bytes()).collect();
+    let score = RamValue::new(0x300, 3, Encoding::Bcd);
+    let mut env = Env::new(&rom, move |emu: &HeadlessEmu| score.read(emu.get_ram()) as f32);
+    env.step(1 << 5);
+
+    let mut fork = env.clone();
+    fork.step(0);
+    fork.step(1 << 5);
+    assert_eq!(fork.step(0).reward, 2.0);
+    assert_eq!(env.step(0).reward, 1.0);
+    assert_eq!(env.observation(), fork.observation());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn ram_values_decode() {
+    let ram = [0x01, 0x02, 0x09, 0x05];
+    assert_eq!(RamValue::new(0, 2, Encoding::Binary).read(&ram), 0x0102);
+    assert_eq!(RamValue::new(1, 3, Encoding::Bcd).read(&ram), 295);
+    assert_eq!(RamValue::new(3, 2, Encoding::Binary).read(&ram), 0x0500);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn game_rules_are_evaluated_every_frame() {
+    let rom: Vec<u8> = SCORING_ROM.iter().flat_map(|op| op.to_be_bytes()).collect();
+    let mut emu = Emu::headless();
+    emu.load_rom(&rom);
+    assert_eq!(emu.game_status(), None);
+
+    emu.set_game_rules(Some(GameRules {
+        score: Some(GameValue::Ram(RamValue::new(0x300, 3, Encoding::Bcd))),
+        lives: None,
+        game_over: None,
+        won: Some(Condition { value: GameValue::Register(0), equals: 2 }),
+    }));
+    let status = emu.game_status().unwrap();
+    assert_eq!((status.score, status.lives, status.is_finished()), (Some(0), None, false));
+    let start = emu.save_state();
+
+    for pressed in [true, false, true] {
+        emu.keypress(5, pressed);
+        emu.run_frame(10);
+    }
+    let status = emu.game_status().unwrap();
+    assert_eq!(status.score, Some(2));
+    assert!(status.won && !status.game_over);
+
+    // loading a state goes back to that point of the game
+    emu.load_state(&start).unwrap();
+    let status = emu.game_status().unwrap();
+    assert_eq!((status.score, status.won), (Some(0), false));
+
+    emu.reset();
+    assert_eq!(emu.game_status().unwrap().score, Some(0));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn bundled_games_are_found_by_hash() {
+    let ufo = include_bytes!("../../roms/UFO.ch8");
+    let game = find_game(&rom_hash(ufo)).unwrap();
+    assert_eq!(game.title, "UFO");
+    assert!(find_game(&rom_hash(&[0x12, 0x00])).is_none());
+
+    // V8 is 0 until the game starts, which isn't a game over
+    let mut env = Env::for_game(ufo).unwrap();
+    assert!(!env.emu().game_status().unwrap().game_over);
+    assert!(!env.step(0).terminated);
+    let status = env.emu().game_status().unwrap();
+    assert_eq!((status.score, status.lives), (Some(0), Some(15)));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn game_lists_are_checked() {
+    let games = parse_games("[[game]]\ntitle = \"T\"\nsha1 = \"AB\"\nscore = { addr = 0x300, len = 2 }\n").unwrap();
+    assert_eq!(games[0].sha1, "ab");
+    assert_eq!(games[0].rules.score, Some(GameValue::Ram(RamValue::new(0x300, 2, Encoding::Binary))));
+
+    for bad in ["score = { register = 16 }", "score = { addr = 0xFFF, len = 2 }", "lives = { addr = 0, len = 5 }", "won = { register = 1 }"] {
+        assert!(parse_games(&format!("[[game]]\ntitle = \"T\"\nsha1 = \"ab\"\n{bad}\n")).is_err(), "{bad}");
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn bundled_database_sets_quirks() {
+    let blinky = include_bytes!("../../roms/BLINKY.ch8");
+    let info = RomDatabase::bundled().lookup(&rom_hash(blinky)).unwrap();
+    assert_eq!(info.title, "Blinky");
+    assert_eq!(info.platforms, ["chip48"]);
+    assert_eq!(info.ipf, Some(20));
+
+    let mut emu = HeadlessEmu::headless();
+    assert_eq!(emu.load_rom_with_info(blinky, RomDatabase::bundled()), Some(info));
+    assert_eq!(emu.get_quirks(), Quirks { memory_increment: true, memory_increment_by_x: true, ..Quirks::SCHIP });
+
+    let wipe_off = include_bytes!("../../roms/WIPEOFF.ch8");
+    assert!(emu.load_rom_with_info(wipe_off, RomDatabase::bundled()).is_some());
+    assert_eq!(emu.get_quirks(), Quirks::COSMAC_VIP);
+    assert!(emu.game_status().is_some());
+
+    assert!(emu.load_rom_with_info(&[0x12, 0x00], RomDatabase::bundled()).is_none());
+    assert_eq!(emu.get_quirks(), Quirks::COSMAC_VIP);
+    assert!(emu.game_status().is_none());
+}
+
+// The bundled database and `analyze_rom` should agree on which ROMs need
+// CHIP-48, or the GUI applies quirks that the ROM's code doesn't ask for.
+#[cfg(feature = "std")]
+#[test]
+fn bundled_database_agrees_with_analysis() {
+    let roms = std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/../roms")).unwrap();
+    for path in roms.map(|entry| entry.unwrap().path()) {
+        let rom = std::fs::read(&path).unwrap();
+        if let Some(info) = RomDatabase::bundled().lookup(&rom_hash(&rom)) {
+            let chip48 = analyze_rom(&rom).platform() == Platform::Chip48;
+            assert_eq!(info.platforms.iter().any(|id| id == "chip48"), chip48, "{}", path.display());
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn database_roms_override_their_platform() {
+    let programs = r##"[{ "title": "T", "authors": ["A"], "roms": { "AB": {
+        "platforms": ["vip", "chip48"],
+        "quirkyPlatforms": { "vip": { "shift": true } },
+        "colors": { "pixels": ["#000000", "#ff8000"] },
+        "keys": { "left": 4, "right": 6 },
+        "unknown": 1
+    } } }]"##;
+    let platforms = r#"[{ "id": "vip", "defaultTickrate": 12, "quirks": { "logic": true, "wrap": true, "vblank": true } }]"#;
+    let database = RomDatabase::from_json(programs, r#"{ "ab": 0 }"#, platforms).unwrap();
+
+    let info = database.lookup("AB").unwrap();
+    assert_eq!(info.authors, ["A"]);
+    assert_eq!(info.ipf, Some(12));
+    assert_eq!(
+        info.quirks,
+        Some(Quirks { vf_reset: true, memory_increment: true, shift_uses_vy: false, jump_uses_vx: false, clip_sprites: false, ..Quirks::default() })
+    );
+    assert_eq!(info.colors, Some(RomColors { background: [0, 0, 0], foreground: [0xFF, 0x80, 0] }));
+    assert_eq!((info.keys.left, info.keys.right, info.keys.up), (Some(4), Some(6), None));
+    assert!(database.lookup("cd").is_none());
+
+    assert!(RomDatabase::from_json(programs, r#"{ "ab": 1 }"#, platforms).is_err());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn analysis_finds_platform_features() {
+    let analyze = |program: &[u16]| analyze_rom(&program.iter().flat_map(|op| op.to_be_bytes()).collect::<Vec<u8>>());
+
+    // 00FF after the jump is data
+    let plain = analyze(&[0x1204, 0x00FF, 0x1204]);
+    assert_eq!((plain.platform(), plain.code_bytes), (Platform::Chip8, 4));
+    assert_eq!(plain.quirks(), Quirks::default());
+
+    let schip = analyze(&[0x00FF, 0xD120, 0x1204]);
+    assert_eq!((schip.platform(), schip.schip), (Platform::SuperChip, Finding { count: 2, first: Some(0x200) }));
+    assert!(!schip.platform().is_supported());
+
+    // F000 NNNN is 4 bytes long
+    let xochip = analyze(&[0xF000, 0x0300, 0x1204]);
+    assert_eq!((xochip.platform(), xochip.code_bytes, xochip.unknown.count), (Platform::XoChip, 6, 0));
+
+    // stores 0x12 (JP) over the instruction at 0x208
+    let self_modifying = analyze(&[0xA208, 0x6012, 0xF055, 0x1208, 0x1208]);
+    assert_eq!(self_modifying.self_modifying.first, Some(0x208));
+    assert_eq!(self_modifying.quirks(), Quirks::COSMAC_VIP);
+
+    // stores V0 and V1 to 0x300, 0x302... as I moves on
+    let store_loop = analyze(&[0xA300, 0xF155, 0x7001, 0x3005, 0x1202, 0x8126, 0x120C]);
+    assert_eq!(store_loop.memory_loops.first, Some(0x202));
+    assert_eq!(store_loop.shifts_vy.first, Some(0x20A));
+    assert_eq!(store_loop.platform(), Platform::CosmacVip);
+
+    // increments the byte at 0x300, which needs I to stay there
+    let read_modify_write = analyze(&[0xA300, 0xF065, 0x7001, 0xF055, 0x8106, 0x120A]);
+    assert_eq!(read_modify_write.load_then_store.first, Some(0x206));
+    assert_eq!(read_modify_write.shifts_in_place.first, Some(0x208));
+    assert_eq!(read_modify_write.platform(), Platform::Chip48);
+    assert_eq!(read_modify_write.quirks(), Quirks::SCHIP);
+
+    let unknown = analyze(&[0x6001, 0x5121]);
+    assert_eq!(unknown.unknown.first, Some(0x202));
+}
+
+#[cfg(feature = "std")]
+proptest! {
+    #[test]
+    fn analysis_handles_any_rom(rom in proptest::collection::vec(any::<u8>(), 0..MAX_ROM_SIZE + 2)) {
+        let analysis = analyze_rom(&rom);
+        prop_assert!(analysis.code_bytes <= rom.len().min(MAX_ROM_SIZE));
+    }
+}
@@ -0,0 +1,80 @@
+use crate::instructions::Decoded;
+
+/// How much work the emulator does per 60 Hz frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Timing {
+    /// A fixed number of instructions per frame, whatever they are.
+    #[default]
+    Instructions,
+    /// Each instruction costs what it took on the COSMAC VIP interpreter, and
+    /// DXYN waits for the vertical blank before drawing.
+    CosmacVip,
+}
+
+// The VIP's 1802 runs at 1.7609 MHz with 8 clocks per machine cycle, so a
+// 60 Hz frame is ~3668 machine cycles. The 1861 video chip steals one
+// cycle per displayed byte (8 bytes x 128 lines) and the interrupt routine
+// that services it and the timers needs a few more.
+const VIP_CYCLES_PER_FRAME: i32 = 3668;
+const VIP_DISPLAY_DMA_CYCLES: i32 = 8 * 128;
+const VIP_INTERRUPT_CYCLES: i32 = 46;
+pub(crate) const VIP_CYCLES_FOR_PROGRAM: i32 = VIP_CYCLES_PER_FRAME - VIP_DISPLAY_DMA_CYCLES - VIP_INTERRUPT_CYCLES;
+
+// Fetching and dispatching an instruction, paid by every opcode
+const VIP_FETCH_CYCLES: i32 = 40;
+// Extra cost of a skip instruction when the skip is taken
+const VIP_SKIP_CYCLES: i32 = 4;
+
+/// Machine cycles the VIP interpreter spends on `instruction`, once executed.
+/// `v_reg` is the register file after execution and `skipped` tells whether a
+/// skip instruction skipped. The costs are approximations of the original
+/// interpreter routines; only 00E0 and DXYN depend on the screen in reality.
+pub(crate) fn vip_cycles(instruction: Decoded, v_reg: &[u8], skipped: bool) -> i32 {
+    let skip = if skipped { VIP_SKIP_CYCLES } else { 0 };
+
+    let exec = match instruction {
+        Decoded::NOP                => 0,
+        Decoded::ClearScreen        => 3078,
+        Decoded::RET                => 10,
+        Decoded::Jump(_)            => 12,
+        Decoded::Call(_)            => 26,
+        Decoded::SkipEq(_, _)       => 10 + skip,
+        Decoded::SkipNeq(_, _)      => 10 + skip,
+        Decoded::SkipEqReg(_, _)    => 14 + skip,
+        Decoded::SetReg(_, _)       => 6,
+        Decoded::AddReg(_, _)       => 10,
+        Decoded::SetRegReg(_, _)
+        | Decoded::Or(_, _)
+        | Decoded::And(_, _)
+        | Decoded::Xor(_, _)
+        | Decoded::AddRegReg(_, _)
+        | Decoded::SubRegReg(_, _)
+        | Decoded::RightShift(_, _)
+        | Decoded::SubRegRegRev(_, _)
+        | Decoded::LeftShift(_, _)  => 44,
+        Decoded::SkipNeqReg(_, _)   => 14 + skip,
+        Decoded::SetIReg(_)         => 12,
+        Decoded::JumpOffset(_)      => 22,
+        Decoded::Rand(_, _)         => 36,
+        // each row is shifted into place and XORed into two display bytes
+        Decoded::Draw(_, _, rows)   => 26 + 46 * rows as i32,
+        Decoded::SkipKey(_)         => 14 + skip,
+        Decoded::SkipNKey(_)        => 14 + skip,
+        Decoded::GetDelay(_)        => 10,
+        Decoded::WaitKey(_)         => 10, // per poll, the instruction repeats
+        Decoded::SetDelay(_)        => 10,
+        Decoded::SetSound(_)        => 10,
+        Decoded::AddIReg(_)         => 16,
+        Decoded::SetIRegFont(_)     => 16,
+        // BCD is done by repeated subtraction, one loop per unit of each digit
+        Decoded::StoreBCD(x) => {
+            let value = v_reg[x as usize];
+            let digit_sum = value / 100 + (value / 10) % 10 + value % 10;
+            84 + 16 * digit_sum as i32
+        },
+        Decoded::StoreRegsToMem(x)  => 14 + 14 * (x as i32 + 1),
+        Decoded::LoadMemToRegs(x)   => 14 + 14 * (x as i32 + 1),
+    };
+
+    VIP_FETCH_CYCLES + exec
+}
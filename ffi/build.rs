@@ -0,0 +1,25 @@
+// Generates chip8.h from the exported functions into OUT_DIR. The checked-in
+// copy, include/chip8.h, is only rewritten when CHIP8_UPDATE_HEADER is set,
+// so builds leave the source tree alone; tests/header.rs checks that the
+// two match.
+
+fn main() {
+    let dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    println!("cargo::rerun-if-changed=src/lib.rs");
+    println!("cargo::rerun-if-changed=cbindgen.toml");
+    println!("cargo::rerun-if-env-changed=CHIP8_UPDATE_HEADER");
+
+    let config = cbindgen::Config::from_file(format!("{dir}/cbindgen.toml")).expect("cbindgen.toml");
+    match cbindgen::generate_with_config(&dir, config) {
+        Ok(bindings) => {
+            bindings.write_to_file(format!("{out_dir}/chip8.h"));
+            if std::env::var_os("CHIP8_UPDATE_HEADER").is_some() {
+                bindings.write_to_file(format!("{dir}/include/chip8.h"));
+            }
+        },
+        Err(e) => {
+            println!("cargo::warning=Can't generate chip8.h: {e}");
+        },
+    }
+}
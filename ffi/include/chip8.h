@@ -0,0 +1,160 @@
+#ifndef CHIP8_H
+#define CHIP8_H
+
+/* Generated by cbindgen from ffi/src/lib.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define CHIP8_SCREEN_WIDTH 64
+
+#define CHIP8_SCREEN_HEIGHT 32
+
+#define CHIP8_NUM_KEYS 16
+
+/**
+ * Size of the buffer `chip8_save_state` needs.
+ */
+#define CHIP8_SAVE_STATE_SIZE 4432
+
+typedef enum Chip8Status {
+  CHIP8_STATUS_OK = 0,
+  CHIP8_STATUS_NULL_POINTER,
+  /**
+   * The ROM doesn't fit in memory after the start address.
+   */
+  CHIP8_STATUS_ROM_TOO_LARGE,
+  /**
+   * Not a key from 0 to 15.
+   */
+  CHIP8_STATUS_INVALID_KEY,
+  /**
+   * Not a known quirk profile.
+   */
+  CHIP8_STATUS_UNKNOWN_QUIRKS,
+  CHIP8_STATUS_BUFFER_TOO_SMALL,
+  /**
+   * Not a save state from this version of the library.
+   */
+  CHIP8_STATUS_INVALID_STATE,
+  /**
+   * The program did something the emulator can't go on from, e.g. a
+   * return with an empty stack. Returned by every call running the
+   * machine until `chip8_reset`, `chip8_load_rom` or `chip8_load_state`
+   * succeeds.
+   */
+  CHIP8_STATUS_CRASHED,
+} Chip8Status;
+
+/**
+ * An emulated machine, created by `chip8_new` and destroyed by `chip8_free`.
+ */
+typedef struct Chip8Emu Chip8Emu;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * A new machine with no ROM, default quirks and instruction-count timing.
+ */
+struct Chip8Emu *chip8_new(void);
+
+void chip8_free(struct Chip8Emu *emu);
+
+/**
+ * Resets the machine and loads `len` bytes from `rom` at 0x200.
+ */
+enum Chip8Status chip8_load_rom(struct Chip8Emu *emu, const uint8_t *rom, size_t len);
+
+/**
+ * Restarts the last ROM loaded, keeping the quirks and timing.
+ */
+enum Chip8Status chip8_reset(struct Chip8Emu *emu);
+
+/**
+ * Runs one instruction.
+ */
+enum Chip8Status chip8_step(struct Chip8Emu *emu);
+
+/**
+ * Counts the delay and sound timers down, call at 60 Hz between steps.
+ */
+enum Chip8Status chip8_tick_timers(struct Chip8Emu *emu);
+
+/**
+ * Runs one 60 Hz frame: `ipf` instructions (or, with COSMAC VIP timing,
+ * as many as fit in a frame) followed by a timer tick.
+ */
+enum Chip8Status chip8_run_frame(struct Chip8Emu *emu, size_t ipf);
+
+/**
+ * Presses (`pressed` true) or releases key 0 to 15.
+ */
+enum Chip8Status chip8_set_key(struct Chip8Emu *emu, size_t key, bool pressed);
+
+/**
+ * Selects a quirk profile: "default", "vip" or "schip".
+ */
+enum Chip8Status chip8_set_quirks(struct Chip8Emu *emu, const char *profile);
+
+/**
+ * Switches between `ipf` instructions per frame (false, the default) and
+ * the COSMAC VIP's instruction timing (true).
+ */
+enum Chip8Status chip8_set_vip_timing(struct Chip8Emu *emu, bool vip);
+
+/**
+ * Seeds the random number generator, for reproducible runs.
+ */
+enum Chip8Status chip8_set_seed(struct Chip8Emu *emu, uint64_t seed);
+
+/**
+ * Copies the screen to `pixels`, one byte per pixel (0 or 1) row by row,
+ * `CHIP8_SCREEN_WIDTH * CHIP8_SCREEN_HEIGHT` bytes.
+ */
+enum Chip8Status chip8_framebuffer(const struct Chip8Emu *emu, uint8_t *pixels, size_t len);
+
+/**
+ * The screen as `CHIP8_SCREEN_HEIGHT` packed rows, the most significant
+ * bit being the leftmost pixel. Valid until the next call changing the
+ * machine. NULL for a NULL handle.
+ */
+const uint64_t *chip8_screen_rows(const struct Chip8Emu *emu);
+
+/**
+ * True if the screen changed since the last call, so it needs redrawing.
+ */
+bool chip8_take_dirty(struct Chip8Emu *emu);
+
+/**
+ * True while the sound timer runs, i.e. the beep should be audible.
+ */
+bool chip8_is_beeping(const struct Chip8Emu *emu);
+
+/**
+ * Writes the machine's state to `buffer`, `CHIP8_SAVE_STATE_SIZE` bytes.
+ * Quirks and timing aren't included.
+ */
+enum Chip8Status chip8_save_state(const struct Chip8Emu *emu, uint8_t *buffer, size_t len);
+
+/**
+ * Restores a state written by `chip8_save_state`. On error the machine is
+ * left as it was.
+ */
+enum Chip8Status chip8_load_state(struct Chip8Emu *emu, const uint8_t *buffer, size_t len);
+
+/**
+ * A static English description of `status`, for error messages. Values
+ * that aren't a `Chip8Status` get a generic message.
+ */
+const char *chip8_status_message(int status);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CHIP8_H */
@@ -0,0 +1,321 @@
+// C ABI for embedding the emulator in C and C++ programs. The header,
+// include/chip8.h, is generated from this file by build.rs.
+//
+// Every function taking a handle accepts NULL and returns
+// CHIP8_STATUS_NULL_POINTER (or does nothing) rather than crashing. Other
+// pointers must be valid for the length passed with them, and handles must
+// come from chip8_new and not be used after chip8_free.
+
+#![allow(clippy::missing_safety_doc)]
+
+use std::cell::Cell;
+use std::ffi::{c_char, c_int, CStr};
+use std::panic::{self, catch_unwind, AssertUnwindSafe};
+use std::sync::Once;
+
+use chip8_core::{Quirks, Timing, MAX_ROM_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH, STATE_SIZE};
+
+// Literals, cbindgen can't see the core's values
+pub const CHIP8_SCREEN_WIDTH: usize = 64;
+pub const CHIP8_SCREEN_HEIGHT: usize = 32;
+pub const CHIP8_NUM_KEYS: usize = 16;
+/// Size of the buffer `chip8_save_state` needs.
+pub const CHIP8_SAVE_STATE_SIZE: usize = 4432;
+
+const _: () = assert!(CHIP8_SCREEN_WIDTH == SCREEN_WIDTH && CHIP8_SCREEN_HEIGHT == SCREEN_HEIGHT);
+const _: () = assert!(CHIP8_SAVE_STATE_SIZE == STATE_SIZE);
+
+/// An emulated machine, created by `chip8_new` and destroyed by `chip8_free`.
+pub struct Chip8Emu {
+    emu: chip8_core::Emu,
+    // the last ROM loaded, for chip8_reset
+    rom: Vec<u8>,
+    // set when the program crashed the machine, until it is reset or loaded
+    crashed: bool,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Status {
+    Ok = 0,
+    NullPointer,
+    /// The ROM doesn't fit in memory after the start address.
+    RomTooLarge,
+    /// Not a key from 0 to 15.
+    InvalidKey,
+    /// Not a known quirk profile.
+    UnknownQuirks,
+    BufferTooSmall,
+    /// Not a save state from this version of the library.
+    InvalidState,
+    /// The program did something the emulator can't go on from, e.g. a
+    /// return with an empty stack. Returned by every call running the
+    /// machine until `chip8_reset`, `chip8_load_rom` or `chip8_load_state`
+    /// succeeds.
+    Crashed,
+}
+
+impl Chip8Status {
+    const ALL: [Chip8Status; 8] = [
+        Chip8Status::Ok,
+        Chip8Status::NullPointer,
+        Chip8Status::RomTooLarge,
+        Chip8Status::InvalidKey,
+        Chip8Status::UnknownQuirks,
+        Chip8Status::BufferTooSmall,
+        Chip8Status::InvalidState,
+        Chip8Status::Crashed,
+    ];
+}
+
+/// A new machine with no ROM, default quirks and instruction-count timing.
+#[unsafe(no_mangle)]
+pub extern "C" fn chip8_new() -> *mut Chip8Emu {
+    Box::into_raw(Box::new(Chip8Emu { emu: chip8_core::Emu::new(), rom: Vec::new(), crashed: false }))
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_free(emu: *mut Chip8Emu) {
+    if !emu.is_null() {
+        drop(unsafe { Box::from_raw(emu) });
+    }
+}
+
+/// Resets the machine and loads `len` bytes from `rom` at 0x200.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_load_rom(emu: *mut Chip8Emu, rom: *const u8, len: usize) -> Chip8Status {
+    let Some(emu) = (unsafe { emu.as_mut() }) else {
+        return Chip8Status::NullPointer;
+    };
+    if rom.is_null() && len > 0 {
+        return Chip8Status::NullPointer;
+    }
+    if len > MAX_ROM_SIZE {
+        return Chip8Status::RomTooLarge;
+    }
+    let rom = match len {
+        0 => &[][..],
+        _ => unsafe { std::slice::from_raw_parts(rom, len) },
+    };
+    emu.rom = rom.to_vec();
+    emu.emu.reset();
+    emu.emu.load_rom(rom);
+    emu.crashed = false;
+    Chip8Status::Ok
+}
+
+/// Restarts the last ROM loaded, keeping the quirks and timing.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_reset(emu: *mut Chip8Emu) -> Chip8Status {
+    let Some(emu) = (unsafe { emu.as_mut() }) else {
+        return Chip8Status::NullPointer;
+    };
+    emu.emu.reset();
+    emu.emu.load_rom(&emu.rom);
+    emu.crashed = false;
+    Chip8Status::Ok
+}
+
+/// Runs one instruction.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_step(emu: *mut Chip8Emu) -> Chip8Status {
+    unsafe { run(emu, |emu| emu.tick()) }
+}
+
+/// Counts the delay and sound timers down, call at 60 Hz between steps.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_tick_timers(emu: *mut Chip8Emu) -> Chip8Status {
+    unsafe { run(emu, |emu| emu.tick_timers()) }
+}
+
+/// Runs one 60 Hz frame: `ipf` instructions (or, with COSMAC VIP timing,
+/// as many as fit in a frame) followed by a timer tick.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_run_frame(emu: *mut Chip8Emu, ipf: usize) -> Chip8Status {
+    unsafe { run(emu, |emu| emu.run_frame(ipf)) }
+}
+
+thread_local! {
+    // set while `run` is catching panics, which are then reported as
+    // CHIP8_STATUS_CRASHED instead of printed
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+}
+
+// Wraps the panic hook once, keeping the embedder's stderr free of Rust panic
+// messages for the panics we turn into a status. Other panics are still
+// printed by the previous hook.
+fn quiet_caught_panics() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !CATCHING.get() {
+                previous(info);
+            }
+        }));
+    });
+}
+
+// Runs `f`, turning a panic in the core into an error for the caller, since
+// unwinding into C is undefined. A crashed machine isn't run again until it
+// is reset or loaded.
+unsafe fn run(emu: *mut Chip8Emu, f: impl FnOnce(&mut chip8_core::Emu)) -> Chip8Status {
+    let Some(emu) = (unsafe { emu.as_mut() }) else {
+        return Chip8Status::NullPointer;
+    };
+    if emu.crashed {
+        return Chip8Status::Crashed;
+    }
+    quiet_caught_panics();
+    CATCHING.set(true);
+    let result = catch_unwind(AssertUnwindSafe(|| f(&mut emu.emu)));
+    CATCHING.set(false);
+    match result {
+        Ok(()) => Chip8Status::Ok,
+        Err(_) => {
+            emu.crashed = true;
+            Chip8Status::Crashed
+        },
+    }
+}
+
+/// Presses (`pressed` true) or releases key 0 to 15.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_set_key(emu: *mut Chip8Emu, key: usize, pressed: bool) -> Chip8Status {
+    let Some(emu) = (unsafe { emu.as_mut() }) else {
+        return Chip8Status::NullPointer;
+    };
+    if key >= CHIP8_NUM_KEYS {
+        return Chip8Status::InvalidKey;
+    }
+    emu.emu.keypress(key, pressed);
+    Chip8Status::Ok
+}
+
+/// Selects a quirk profile: "default", "vip" or "schip".
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_set_quirks(emu: *mut Chip8Emu, profile: *const c_char) -> Chip8Status {
+    let (Some(emu), false) = (unsafe { emu.as_mut() }, profile.is_null()) else {
+        return Chip8Status::NullPointer;
+    };
+    let profile = unsafe { CStr::from_ptr(profile) }.to_str().ok();
+    match profile.and_then(Quirks::from_name) {
+        Some(quirks) => {
+            emu.emu.set_quirks(quirks);
+            Chip8Status::Ok
+        },
+        None => Chip8Status::UnknownQuirks,
+    }
+}
+
+/// Switches between `ipf` instructions per frame (false, the default) and
+/// the COSMAC VIP's instruction timing (true).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_set_vip_timing(emu: *mut Chip8Emu, vip: bool) -> Chip8Status {
+    let Some(emu) = (unsafe { emu.as_mut() }) else {
+        return Chip8Status::NullPointer;
+    };
+    emu.emu.set_timing(if vip { Timing::CosmacVip } else { Timing::Instructions });
+    Chip8Status::Ok
+}
+
+/// Seeds the random number generator, for reproducible runs.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_set_seed(emu: *mut Chip8Emu, seed: u64) -> Chip8Status {
+    let Some(emu) = (unsafe { emu.as_mut() }) else {
+        return Chip8Status::NullPointer;
+    };
+    emu.emu.set_seed(seed);
+    Chip8Status::Ok
+}
+
+/// Copies the screen to `pixels`, one byte per pixel (0 or 1) row by row,
+/// `CHIP8_SCREEN_WIDTH * CHIP8_SCREEN_HEIGHT` bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_framebuffer(emu: *const Chip8Emu, pixels: *mut u8, len: usize) -> Chip8Status {
+    let (Some(emu), false) = (unsafe { emu.as_ref() }, pixels.is_null()) else {
+        return Chip8Status::NullPointer;
+    };
+    if len < SCREEN_WIDTH * SCREEN_HEIGHT {
+        return Chip8Status::BufferTooSmall;
+    }
+    let pixels = unsafe { std::slice::from_raw_parts_mut(pixels, SCREEN_WIDTH * SCREEN_HEIGHT) };
+    emu.emu.display().write_bytes(pixels);
+    Chip8Status::Ok
+}
+
+/// The screen as `CHIP8_SCREEN_HEIGHT` packed rows, the most significant
+/// bit being the leftmost pixel. Valid until the next call changing the
+/// machine. NULL for a NULL handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_screen_rows(emu: *const Chip8Emu) -> *const u64 {
+    match unsafe { emu.as_ref() } {
+        Some(emu) => emu.emu.get_screen().as_ptr(),
+        None => std::ptr::null(),
+    }
+}
+
+/// True if the screen changed since the last call, so it needs redrawing.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_take_dirty(emu: *mut Chip8Emu) -> bool {
+    unsafe { emu.as_mut() }.is_some_and(|emu| emu.emu.take_dirty().is_some())
+}
+
+/// True while the sound timer runs, i.e. the beep should be audible.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_is_beeping(emu: *const Chip8Emu) -> bool {
+    unsafe { emu.as_ref() }.is_some_and(|emu| emu.emu.is_beeping())
+}
+
+/// Writes the machine's state to `buffer`, `CHIP8_SAVE_STATE_SIZE` bytes.
+/// Quirks and timing aren't included.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_save_state(emu: *const Chip8Emu, buffer: *mut u8, len: usize) -> Chip8Status {
+    let (Some(emu), false) = (unsafe { emu.as_ref() }, buffer.is_null()) else {
+        return Chip8Status::NullPointer;
+    };
+    if len < STATE_SIZE {
+        return Chip8Status::BufferTooSmall;
+    }
+    let state = emu.emu.save_state();
+    unsafe { std::ptr::copy_nonoverlapping(state.as_ptr(), buffer, STATE_SIZE) };
+    Chip8Status::Ok
+}
+
+/// Restores a state written by `chip8_save_state`. On error the machine is
+/// left as it was.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn chip8_load_state(emu: *mut Chip8Emu, buffer: *const u8, len: usize) -> Chip8Status {
+    let (Some(emu), false) = (unsafe { emu.as_mut() }, buffer.is_null()) else {
+        return Chip8Status::NullPointer;
+    };
+    let state = unsafe { std::slice::from_raw_parts(buffer, len) };
+    match emu.emu.load_state(state) {
+        Ok(()) => {
+            emu.crashed = false;
+            Chip8Status::Ok
+        },
+        Err(_) => Chip8Status::InvalidState,
+    }
+}
+
+/// A static English description of `status`, for error messages. Values
+/// that aren't a `Chip8Status` get a generic message.
+#[unsafe(no_mangle)]
+pub extern "C" fn chip8_status_message(status: c_int) -> *const c_char {
+    // an out-of-range value in a Rust enum is undefined, so C passes an int
+    let Some(&status) = Chip8Status::ALL.iter().find(|&&known| known as c_int == status) else {
+        return c"unknown status".as_ptr();
+    };
+    let message = match status {
+        Chip8Status::Ok => c"success",
+        Chip8Status::NullPointer => c"null pointer",
+        Chip8Status::RomTooLarge => c"ROM too large",
+        Chip8Status::InvalidKey => c"invalid key",
+        Chip8Status::UnknownQuirks => c"unknown quirk profile",
+        Chip8Status::BufferTooSmall => c"buffer too small",
+        Chip8Status::InvalidState => c"invalid save state",
+        Chip8Status::Crashed => c"the program crashed the emulator",
+    };
+    message.as_ptr()
+}
@@ -0,0 +1,9 @@
+// The checked-in header must match the one build.rs generates from the
+// current code. `CHIP8_UPDATE_HEADER=1 cargo build` updates it.
+
+#[test]
+fn checked_in_header_is_up_to_date() {
+    let generated = std::fs::read_to_string(concat!(env!("OUT_DIR"), "/chip8.h")).expect("generated header");
+    let checked_in = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/include/chip8.h")).expect("include/chip8.h");
+    assert!(generated == checked_in, "include/chip8.h is out of date, run `CHIP8_UPDATE_HEADER=1 cargo build`");
+}
@@ -0,0 +1,148 @@
+/* Exercises the C API against the static library, see the Makefile. */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "chip8.h"
+
+static int failures = 0;
+
+#define CHECK(cond) \
+    do { \
+        if (!(cond)) { \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+            failures++; \
+        } \
+    } while (0)
+
+#define CHECK_STATUS(call, expected) \
+    do { \
+        enum Chip8Status status = (call); \
+        if (status != (expected)) { \
+            fprintf(stderr, "%s:%d: %s returned \"%s\", expected \"%s\"\n", __FILE__, __LINE__, #call, \
+                    chip8_status_message(status), chip8_status_message(expected)); \
+            failures++; \
+        } \
+    } while (0)
+
+static int lit_pixels(const Chip8Emu *emu) {
+    uint8_t pixels[CHIP8_SCREEN_WIDTH * CHIP8_SCREEN_HEIGHT];
+    int count = 0;
+    CHECK_STATUS(chip8_framebuffer(emu, pixels, sizeof pixels), CHIP8_STATUS_OK);
+    for (size_t i = 0; i < sizeof pixels; i++) {
+        count += pixels[i];
+    }
+    return count;
+}
+
+static void test_drawing(void) {
+    /* LD V0, 0xA; SKP V0; JP 0x202; DRW V1, V1, 5 (the "0" glyph); JP 0x208 */
+    const uint8_t rom[] = {0x60, 0x0A, 0xE0, 0x9E, 0x12, 0x02, 0xD1, 0x15, 0x12, 0x08};
+    Chip8Emu *emu = chip8_new();
+    CHECK_STATUS(chip8_load_rom(emu, rom, sizeof rom), CHIP8_STATUS_OK);
+    CHECK(chip8_take_dirty(emu)); /* a fresh screen needs drawing once */
+
+    CHECK_STATUS(chip8_run_frame(emu, 10), CHIP8_STATUS_OK);
+    CHECK(!chip8_take_dirty(emu));
+    CHECK(lit_pixels(emu) == 0);
+
+    CHECK_STATUS(chip8_set_key(emu, 0xA, true), CHIP8_STATUS_OK);
+    for (int i = 0; i < 3; i++) {
+        CHECK_STATUS(chip8_step(emu), CHIP8_STATUS_OK);
+    }
+    CHECK(chip8_take_dirty(emu));
+    CHECK(lit_pixels(emu) == 14);
+    /* the top row of the glyph, 0xF0, in the leftmost pixels */
+    CHECK(chip8_screen_rows(emu)[0] == 0xF000000000000000ull);
+    chip8_free(emu);
+}
+
+static void test_timers_and_states(void) {
+    /* LD V0, 3; LD ST, V0; JP 0x204 */
+    const uint8_t rom[] = {0x60, 0x03, 0xF0, 0x18, 0x12, 0x04};
+    static uint8_t state[CHIP8_SAVE_STATE_SIZE];
+    Chip8Emu *emu = chip8_new();
+    CHECK_STATUS(chip8_set_quirks(emu, "vip"), CHIP8_STATUS_OK);
+    CHECK_STATUS(chip8_set_vip_timing(emu, true), CHIP8_STATUS_OK);
+    CHECK_STATUS(chip8_load_rom(emu, rom, sizeof rom), CHIP8_STATUS_OK);
+    CHECK_STATUS(chip8_run_frame(emu, 0), CHIP8_STATUS_OK);
+    CHECK(chip8_is_beeping(emu));
+    CHECK_STATUS(chip8_save_state(emu, state, sizeof state), CHIP8_STATUS_OK);
+
+    CHECK_STATUS(chip8_tick_timers(emu), CHIP8_STATUS_OK);
+    CHECK_STATUS(chip8_tick_timers(emu), CHIP8_STATUS_OK);
+    CHECK_STATUS(chip8_tick_timers(emu), CHIP8_STATUS_OK);
+    CHECK(!chip8_is_beeping(emu));
+
+    /* two ticks of the sound timer were left when saved */
+    CHECK_STATUS(chip8_load_state(emu, state, sizeof state), CHIP8_STATUS_OK);
+    CHECK_STATUS(chip8_tick_timers(emu), CHIP8_STATUS_OK);
+    CHECK(chip8_is_beeping(emu));
+    CHECK_STATUS(chip8_tick_timers(emu), CHIP8_STATUS_OK);
+    CHECK_STATUS(chip8_tick_timers(emu), CHIP8_STATUS_OK);
+    CHECK(!chip8_is_beeping(emu));
+    chip8_free(emu);
+}
+
+static void test_errors(void) {
+    static uint8_t big[4096];
+    uint8_t small[16];
+    const uint8_t ret[] = {0x00, 0xEE}; /* RET with an empty stack */
+    /* LD V0, 1; RET with an empty stack */
+    const uint8_t crash[] = {0x60, 0x01, 0x00, 0xEE};
+    static uint8_t state[CHIP8_SAVE_STATE_SIZE];
+    Chip8Emu *emu = chip8_new();
+
+    CHECK_STATUS(chip8_step(NULL), CHIP8_STATUS_NULL_POINTER);
+    CHECK_STATUS(chip8_load_rom(emu, NULL, 2), CHIP8_STATUS_NULL_POINTER);
+    CHECK_STATUS(chip8_load_rom(emu, big, sizeof big), CHIP8_STATUS_ROM_TOO_LARGE);
+    CHECK_STATUS(chip8_load_rom(emu, big, sizeof big - 0x200), CHIP8_STATUS_OK);
+    CHECK_STATUS(chip8_set_key(emu, CHIP8_NUM_KEYS, true), CHIP8_STATUS_INVALID_KEY);
+    CHECK_STATUS(chip8_set_quirks(emu, "nes"), CHIP8_STATUS_UNKNOWN_QUIRKS);
+    CHECK_STATUS(chip8_framebuffer(emu, small, sizeof small), CHIP8_STATUS_BUFFER_TOO_SMALL);
+    CHECK_STATUS(chip8_save_state(emu, small, sizeof small), CHIP8_STATUS_BUFFER_TOO_SMALL);
+    CHECK_STATUS(chip8_load_state(emu, small, sizeof small), CHIP8_STATUS_INVALID_STATE);
+    CHECK(!chip8_is_beeping(NULL));
+    CHECK(chip8_screen_rows(NULL) == NULL);
+
+    /* reported as a status, without a Rust panic message on stderr */
+    CHECK_STATUS(chip8_load_rom(emu, ret, sizeof ret), CHIP8_STATUS_OK);
+    CHECK_STATUS(chip8_step(emu), CHIP8_STATUS_CRASHED);
+    /* and the machine stays stopped until it is reset or loaded */
+    CHECK_STATUS(chip8_load_rom(emu, crash, sizeof crash), CHIP8_STATUS_OK);
+    CHECK_STATUS(chip8_save_state(emu, state, sizeof state), CHIP8_STATUS_OK);
+    CHECK_STATUS(chip8_step(emu), CHIP8_STATUS_OK);
+    CHECK_STATUS(chip8_step(emu), CHIP8_STATUS_CRASHED);
+    CHECK_STATUS(chip8_step(emu), CHIP8_STATUS_CRASHED);
+    CHECK_STATUS(chip8_run_frame(emu, 10), CHIP8_STATUS_CRASHED);
+    CHECK_STATUS(chip8_tick_timers(emu), CHIP8_STATUS_CRASHED);
+    CHECK_STATUS(chip8_reset(NULL), CHIP8_STATUS_NULL_POINTER);
+    CHECK_STATUS(chip8_reset(emu), CHIP8_STATUS_OK);
+    CHECK_STATUS(chip8_step(emu), CHIP8_STATUS_OK);
+    CHECK_STATUS(chip8_step(emu), CHIP8_STATUS_CRASHED);
+    CHECK_STATUS(chip8_load_state(emu, small, sizeof small), CHIP8_STATUS_INVALID_STATE);
+    CHECK_STATUS(chip8_step(emu), CHIP8_STATUS_CRASHED);
+    CHECK_STATUS(chip8_load_state(emu, state, sizeof state), CHIP8_STATUS_OK);
+    CHECK_STATUS(chip8_step(emu), CHIP8_STATUS_OK);
+    /* and the machine is usable again after loading */
+    CHECK_STATUS(chip8_load_rom(emu, ret, 0), CHIP8_STATUS_OK);
+    CHECK_STATUS(chip8_step(emu), CHIP8_STATUS_OK);
+
+    CHECK(strcmp(chip8_status_message(CHIP8_STATUS_OK), "success") == 0);
+    CHECK(strcmp(chip8_status_message(-1), "unknown status") == 0);
+    CHECK(strcmp(chip8_status_message(CHIP8_STATUS_CRASHED + 1), "unknown status") == 0);
+    chip8_free(emu);
+    chip8_free(NULL);
+}
+
+int main(void) {
+    test_drawing();
+    test_timers_and_states();
+    test_errors();
+    if (failures > 0) {
+        fprintf(stderr, "%d checks failed\n", failures);
+        return 1;
+    }
+    printf("All checks passed\n");
+    return 0;
+}
@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+// Directory under the user's config dir (e.g. ~/.config) for our files
+const CONFIG_DIR: &str = "chip8-emulator";
+pub const CONFIG_FILE: &str = "config.toml";
+
+/// Settings from `config.toml`. Every field is optional in the file and
+/// command line flags override them.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub scale: u32,
+    pub fullscreen: bool,
+    pub integer_scale: bool, // whole window pixels per CHIP-8 pixel
+    pub ipf: Option<usize>, // None = per-ROM default
+    pub speed: f64,
+    pub palette: Option<String>, // None = the ROM's colours, if the database has them
+    pub persistence: f32,
+    pub quirks: Option<String>, // None = the ROM's platform, from the database
+    pub vip_timing: bool,
+    pub volume: f32,
+    pub pitch: f32,
+    pub rom_dir: PathBuf,
+    pub database: Option<PathBuf>, // None = the bundled ROM database
+    pub font: Option<PathBuf>, // TTF font for overlay text, needs the ttf feature
+    pub show_fps: bool,
+    pub screenshot_dir: PathBuf,
+    pub screenshot_scale: Option<u32>, // None = `scale`, also for recordings
+    pub record_raw: bool, // also record raw video and WAV audio for ffmpeg
+    // changes to the default keypad layout, CHIP-8 key in hex -> SDL name
+    pub keys: BTreeMap<String, String>,
+    pub buttons: BTreeMap<String, String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            scale: 16,
+            fullscreen: false,
+            integer_scale: false,
+            ipf: None,
+            speed: 1.0,
+            palette: None,
+            persistence: 0.0,
+            quirks: None,
+            vip_timing: false,
+            volume: 0.2,
+            pitch: 440.0,
+            rom_dir: PathBuf::from("../roms"),
+            database: None,
+            font: None,
+            show_fps: false,
+            screenshot_dir: PathBuf::from("screenshots"),
+            screenshot_scale: None,
+            record_raw: false,
+            keys: BTreeMap::new(),
+            buttons: BTreeMap::new(),
+        }
+    }
+}
+
+/// Our directory in the XDG config dir, if the system has one.
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(CONFIG_DIR))
+}
+
+/// Reads `config.toml`. A missing file gives the defaults; an invalid one is
+/// reported and the emulator exits, like an invalid command line flag.
+pub fn load() -> Config {
+    let Some(path) = config_dir().map(|dir| dir.join(CONFIG_FILE)) else {
+        return Config::default();
+    };
+
+    match fs::read_to_string(&path) {
+        Ok(text) => toml::from_str(&text).unwrap_or_else(|e| {
+            eprintln!("Error in {}: {e}", path.display());
+            std::process::exit(1);
+        }),
+        Err(_) => Config::default(),
+    }
+}
@@ -0,0 +1,323 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use sdl2::controller::Button;
+use sdl2::keyboard::Scancode;
+use serde::{Deserialize, Serialize};
+
+use core::KeyHints;
+
+use crate::config;
+
+pub const NUM_KEYS: usize = 16;
+const KEYMAP_FILE: &str = "keymaps.toml";
+
+// Bindings use scancodes (physical key positions), so the 4x4 block below
+// stays in the same place on AZERTY, QWERTZ, Dvorak...
+//
+//   1 2 3 C        1 2 3 4
+//   4 5 6 D   <=   Q W E R
+//   7 8 9 E        A S D F
+//   A 0 B F        Z X C V
+const DEFAULT_BINDINGS: [Scancode; NUM_KEYS] = [
+    Scancode::X,    // 0
+    Scancode::Num1, // 1
+    Scancode::Num2, // 2
+    Scancode::Num3, // 3
+    Scancode::Q,    // 4
+    Scancode::W,    // 5
+    Scancode::E,    // 6
+    Scancode::A,    // 7
+    Scancode::S,    // 8
+    Scancode::D,    // 9
+    Scancode::Z,    // A
+    Scancode::C,    // B
+    Scancode::Num4, // C
+    Scancode::R,    // D
+    Scancode::F,    // E
+    Scancode::V,    // F
+];
+
+// Game controllers: the D-pad on the 2/4/6/8 cross most games use, A on 5
+const DEFAULT_BUTTONS: [(usize, Button); 5] = [
+    (0x2, Button::DPadUp),
+    (0x8, Button::DPadDown),
+    (0x4, Button::DPadLeft),
+    (0x6, Button::DPadRight),
+    (0x5, Button::A),
+];
+
+struct BuiltinProfile {
+    rom_hash: &'static str,
+    keys: &'static [(usize, Scancode)],
+    buttons: &'static [(usize, Button)],
+}
+
+// Profiles shipped with the emulator, by ROM SHA-1. A profile only lists the
+// keys it changes; the others keep their default binding.
+const BUILTIN_PROFILES: [BuiltinProfile; 3] = [
+    // PONG, PONG2: left paddle on the arrows, right paddle stays on 4 / R
+    BuiltinProfile {
+        rom_hash: "b232ef880bd6060fb45fa6effed7edf0ae95670e",
+        keys: &[(0x1, Scancode::Up), (0x4, Scancode::Down)],
+        buttons: &[(0x1, Button::DPadUp), (0x4, Button::DPadDown)],
+    },
+    BuiltinProfile {
+        rom_hash: "a60611339661e3ab2d8af024ad1da5880a6f8665",
+        keys: &[(0x1, Scancode::Up), (0x4, Scancode::Down)],
+        buttons: &[(0x1, Button::DPadUp), (0x4, Button::DPadDown)],
+    },
+    // INVADERS: move with the left and right arrows, shoot with up
+    BuiltinProfile {
+        rom_hash: "f100197f0f2f05b4f3c8c31ab9c2c3930d3e9571",
+        keys: &[(0x4, Scancode::Left), (0x6, Scancode::Right), (0x5, Scancode::Up)],
+        buttons: &[],
+    },
+];
+
+/// Which physical key, and optionally which controller button, drives each
+/// of the 16 CHIP-8 keys. All connected controllers share the buttons.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    bindings: [Scancode; NUM_KEYS],
+    buttons: [Option<Button>; NUM_KEYS],
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut buttons = [None; NUM_KEYS];
+        for (key, button) in DEFAULT_BUTTONS {
+            buttons[key] = Some(button);
+        }
+        Self { bindings: DEFAULT_BINDINGS, buttons }
+    }
+}
+
+impl Keymap {
+    pub fn chip8_key(&self, scancode: Scancode) -> Option<usize> {
+        self.bindings.iter().position(|&s| s == scancode)
+    }
+
+    /// Binds `key` to `scancode`. If another key already used that scancode,
+    /// the two keys swap bindings so no key is left unreachable.
+    pub fn bind(&mut self, key: usize, scancode: Scancode) {
+        if let Some(other) = self.chip8_key(scancode) {
+            self.bindings[other] = self.bindings[key];
+        }
+        self.bindings[key] = scancode;
+    }
+
+    pub fn chip8_key_for_button(&self, button: Button) -> Option<usize> {
+        self.buttons.iter().position(|&b| b == Some(button))
+    }
+
+    /// Like `bind`, for a controller button.
+    pub fn bind_button(&mut self, key: usize, button: Button) {
+        if let Some(other) = self.chip8_key_for_button(button) {
+            self.buttons[other] = self.buttons[key];
+        }
+        self.buttons[key] = Some(button);
+    }
+
+    // Directions on the arrow keys and the D-pad, actions on A and B. The
+    // second player's keys keep their place on the keypad.
+    fn apply_hints(&mut self, hints: &KeyHints) {
+        let directions = [
+            (hints.up, Scancode::Up, Button::DPadUp),
+            (hints.down, Scancode::Down, Button::DPadDown),
+            (hints.left, Scancode::Left, Button::DPadLeft),
+            (hints.right, Scancode::Right, Button::DPadRight),
+        ];
+        for (key, scancode, button) in directions {
+            if let Some(key) = key.map(usize::from).filter(|&key| key < NUM_KEYS) {
+                self.bind(key, scancode);
+                self.bind_button(key, button);
+            }
+        }
+        for (key, button) in [(hints.a, Button::A), (hints.b, Button::B)] {
+            if let Some(key) = key.map(usize::from).filter(|&key| key < NUM_KEYS) {
+                self.bind_button(key, button);
+            }
+        }
+    }
+
+    fn apply(&mut self, profile: &Profile) {
+        for (key, name) in &profile.keys {
+            match (usize::from_str_radix(key, 16), Scancode::from_name(name)) {
+                (Ok(key), Some(scancode)) if key < NUM_KEYS => self.bind(key, scancode),
+                _ => eprintln!("Ignoring invalid key binding {key} = \"{name}\""),
+            }
+        }
+        for (key, name) in &profile.buttons {
+            match (usize::from_str_radix(key, 16), Button::from_string(name)) {
+                (Ok(key), Some(button)) if key < NUM_KEYS => self.bind_button(key, button),
+                _ => eprintln!("Ignoring invalid button binding {key} = \"{name}\""),
+            }
+        }
+    }
+}
+
+/// A set of bindings as stored on disk: CHIP-8 key in hex -> SDL scancode
+/// name, or SDL game controller button name (`dpup`, `a`, `start`...).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Profile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(default)]
+    keys: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    buttons: BTreeMap<String, String>,
+}
+
+impl Profile {
+    fn from_keymap(title: &str, keymap: &Keymap) -> Self {
+        let keys = (0..NUM_KEYS)
+            .map(|key| (format!("{key:X}"), keymap.bindings[key].name().to_string()))
+            .collect();
+        let buttons = (0..NUM_KEYS)
+            .filter_map(|key| Some((format!("{key:X}"), keymap.buttons[key]?.string())))
+            .collect();
+        Self { title: Some(title.to_string()), keys, buttons }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct KeymapFile {
+    // changes for every ROM, before they moved to `config.toml`. Still
+    // applied under the ones from there, and written back unchanged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default: Option<Profile>,
+    // per-ROM profiles keyed by SHA-1, applied on top of the default
+    #[serde(default)]
+    roms: BTreeMap<String, Profile>,
+}
+
+/// The user's per-ROM key profiles, kept in `keymaps.toml` in the config
+/// directory, plus the changes to the default layout from `config.toml`.
+pub struct KeymapStore {
+    path: Option<PathBuf>,
+    file: KeymapFile,
+    default: Profile,
+}
+
+impl KeymapStore {
+    pub fn load(keys: BTreeMap<String, String>, buttons: BTreeMap<String, String>) -> Self {
+        let path = config::config_dir().map(|dir| dir.join(KEYMAP_FILE));
+
+        let file = match path.as_ref().map(fs::read_to_string) {
+            Some(Ok(text)) => toml::from_str(&text).unwrap_or_else(|e| {
+                eprintln!("Error reading {}, using default keys: {e}", path.as_ref().unwrap().display());
+                KeymapFile::default()
+            }),
+            // a missing file just means nothing was remapped yet
+            _ => KeymapFile::default(),
+        };
+
+        // config.toml wins over the old [default] section for the keys both set
+        let mut default = Profile { title: None, keys, buttons };
+        if let Some(old) = &file.default {
+            eprintln!("The [default] section of {} is deprecated, move it to keys and buttons in {}",
+                      KEYMAP_FILE, config::CONFIG_FILE);
+            for (key, name) in &old.keys {
+                default.keys.entry(key.clone()).or_insert_with(|| name.clone());
+            }
+            for (key, name) in &old.buttons {
+                default.buttons.entry(key.clone()).or_insert_with(|| name.clone());
+            }
+        }
+
+        Self { path, file, default }
+    }
+
+    /// The keymap for a ROM: the default layout, then the user's default
+    /// changes, then the ROM's profile (the user's, a built-in one, or one
+    /// made from the ROM database's key hints).
+    pub fn keymap_for(&self, rom_hash: &str, hints: Option<KeyHints>) -> Keymap {
+        let mut keymap = Keymap::default();
+        keymap.apply(&self.default);
+
+        if let Some(profile) = self.file.roms.get(rom_hash) {
+            keymap.apply(profile);
+        } else if let Some(profile) = BUILTIN_PROFILES.iter().find(|p| p.rom_hash == rom_hash) {
+            for &(key, scancode) in profile.keys {
+                keymap.bind(key, scancode);
+            }
+            for &(key, button) in profile.buttons {
+                keymap.bind_button(key, button);
+            }
+        } else if let Some(hints) = hints {
+            keymap.apply_hints(&hints);
+        }
+        keymap
+    }
+
+    /// Stores `keymap` as the profile of a ROM and writes the file.
+    pub fn save_profile(&mut self, rom_hash: &str, title: &str, keymap: &Keymap) -> Result<(), String> {
+        self.file.roms.insert(rom_hash.to_string(), Profile::from_keymap(title, keymap));
+
+        let path = self.path.as_ref().ok_or("no config directory on this system")?;
+        let text = toml::to_string_pretty(&self.file).map_err(|e| e.to_string())?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        fs::write(path, text).map_err(|e| format!("{}: {e}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_distinct(keymap: &Keymap) {
+        for key in 0..NUM_KEYS {
+            assert_eq!(keymap.chip8_key(keymap.bindings[key]), Some(key), "{:?}", keymap.bindings);
+            if let Some(button) = keymap.buttons[key] {
+                assert_eq!(keymap.chip8_key_for_button(button), Some(key), "{:?}", keymap.buttons);
+            }
+        }
+    }
+
+    #[test]
+    fn binding_a_used_key_swaps() {
+        let mut keymap = Keymap::default();
+        // 5 takes 1's key, 1 gets 5's old one
+        keymap.bind(0x5, Scancode::Num1);
+        assert_eq!(keymap.chip8_key(Scancode::Num1), Some(0x5));
+        assert_eq!(keymap.chip8_key(Scancode::W), Some(0x1));
+        // a new key just replaces the old binding
+        keymap.bind(0x5, Scancode::Up);
+        assert_eq!(keymap.chip8_key(Scancode::Num1), None);
+        assert_distinct(&keymap);
+
+        keymap.bind_button(0x0, Button::A);
+        assert_eq!(keymap.chip8_key_for_button(Button::A), Some(0x0));
+        assert_eq!(keymap.buttons[0x5], None);
+        assert_distinct(&keymap);
+    }
+
+    #[test]
+    fn no_two_keys_share_a_binding() {
+        let scancodes = [DEFAULT_BINDINGS.as_slice(), &[Scancode::Up, Scancode::Down, Scancode::Left, Scancode::Right]].concat();
+        let buttons = [Button::A, Button::B, Button::X, Button::DPadUp, Button::DPadDown, Button::Start];
+        let mut keymap = Keymap::default();
+        // walks every key through every scancode and button, in an order
+        // that keeps hitting keys bound elsewhere
+        for step in 0..scancodes.len() * NUM_KEYS {
+            let key = step * 7 % NUM_KEYS;
+            keymap.bind(key, scancodes[step % scancodes.len()]);
+            keymap.bind_button(key, buttons[step % buttons.len()]);
+            assert_distinct(&keymap);
+        }
+    }
+
+    #[test]
+    fn hints_keep_the_keymap_distinct() {
+        let mut keymap = Keymap::default();
+        let hints = KeyHints { up: Some(0x5), down: Some(0x8), left: Some(0x7), right: Some(0x9), a: Some(0x6), b: Some(0x5), ..KeyHints::default() };
+        keymap.apply_hints(&hints);
+        assert_eq!(keymap.chip8_key(Scancode::Up), Some(0x5));
+        assert_eq!(keymap.chip8_key_for_button(Button::B), Some(0x5));
+        assert_distinct(&keymap);
+    }
+}
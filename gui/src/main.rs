@@ -1,79 +1,186 @@
 use std::fs::File;
 use std::io::Read;
+use std::time::{Duration, Instant};
 
-use sdl2::event::Event;
-use sdl2::pixels::Color;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::pixels::PixelFormatEnum;
 use sdl2::rect::Rect;
-use sdl2::render::Canvas;
-use sdl2::video::Window;
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::{FullscreenType, Window};
 use sdl2::keyboard::Keycode;
-use sdl2::ttf::Font;
 
 use rfd::FileDialog;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use core::*;
+use core::cli::{exit_with, flag_value, quirks_or_exit};
 
-const SCALE: u32 = 16;
-const WINDOW_WIDTH: u32 = (SCREEN_WIDTH as u32) * SCALE;
-const WINDOW_HEIGHT: u32 = (SCREEN_HEIGHT as u32) * SCALE;
-
-const TICKS_PER_FRAME: usize = 10;
-
-fn keymap(key: Keycode) -> Option<usize> {
-    match key {
-        Keycode::Num1 => Some(0x1),
-        Keycode::Num2 => Some(0x2),
-        Keycode::Num3 => Some(0x3),
-        Keycode::Num4 => Some(0xC),
-        Keycode::Q => Some(0x4),
-        Keycode::W => Some(0x5),
-        Keycode::E => Some(0x6),
-        Keycode::R => Some(0xD),
-        Keycode::A => Some(0x7),
-        Keycode::S => Some(0x8),
-        Keycode::D => Some(0x9),
-        Keycode::F => Some(0xE),
-        Keycode::Z => Some(0xA),
-        Keycode::X => Some(0x0),
-        Keycode::C => Some(0xB),
-        Keycode::V => Some(0xF),
-        _ => None,
+mod config;
+mod keymap;
+mod osd;
+mod palette;
+mod phosphor;
+mod screenshot;
+mod text;
+
+use config::Config;
+use keymap::{KeymapStore, NUM_KEYS};
+use palette::{Palette, PALETTES};
+use osd::{Counter, Osd};
+use phosphor::{Phosphor, DECAY_PRESETS, MAX_DECAY, PRESET_NAMES};
+use text::TextRenderer;
+
+// The delay and sound timers always run at 60 Hz; one emulated frame is one
+// timer tick plus `ipf` instructions.
+const TIMER_HZ: u32 = 60;
+const DEFAULT_IPF: usize = 10;
+const MAX_IPF: usize = 1000;
+// Emulated frames we are willing to catch up on in a single loop iteration
+const MAX_FRAMES_PER_UPDATE: u32 = 16;
+
+// Speed multipliers selectable with [ and ]
+const SPEEDS: [f64; 7] = [0.25, 0.5, 0.75, 1.0, 1.5, 2.0, 4.0];
+const NORMAL_SPEED: usize = 3;
+const FAST_FORWARD: f64 = 4.0;
+
+// Keys handled by the emulator before the keypad, so the remap screen
+// doesn't let the keypad use them
+const HOTKEYS: [Keycode; 18] = [
+    Keycode::Escape, Keycode::Return, Keycode::Backspace, Keycode::Tab, Keycode::Space, Keycode::P,
+    Keycode::Minus, Keycode::Equals, Keycode::LeftBracket, Keycode::RightBracket,
+    Keycode::F2, Keycode::F3, Keycode::F4, Keycode::F5, Keycode::F6, Keycode::F9, Keycode::F11, Keycode::F12,
+];
+
+/// Applies the command line flags on top of `config` and returns the ROM
+/// path, if one was given.
+fn parse_args(config: &mut Config) -> Option<PathBuf> {
+    let mut rom_path = None;
+    let mut argv = std::env::args().skip(1);
+
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "--scale" => config.scale = flag_value(&mut argv, &arg, "a window scale factor"),
+            "--fullscreen" => config.fullscreen = true,
+            "--integer-scale" => config.integer_scale = true,
+            "--ipf" => config.ipf = Some(flag_value(&mut argv, &arg, "a number of instructions per frame")),
+            "--speed" => config.speed = flag_value(&mut argv, &arg, "a speed multiplier"),
+            "--palette" => config.palette = Some(flag_value(&mut argv, &arg, "a palette name or BACKGROUND:FOREGROUND hex colours")),
+            "--persistence" => config.persistence = flag_value(&mut argv, &arg, "a decay between 0 (off) and 0.95"),
+            "--quirks" => config.quirks = Some(flag_value(&mut argv, &arg, "a quirk profile")),
+            "--vip-timing" => config.vip_timing = true,
+            "--volume" => config.volume = flag_value(&mut argv, &arg, "a volume between 0 and 1"),
+            "--pitch" => config.pitch = flag_value(&mut argv, &arg, "a frequency in Hz"),
+            "--rom-dir" => config.rom_dir = flag_value(&mut argv, &arg, "a directory"),
+            "--database" => config.database = Some(flag_value(&mut argv, &arg, "a chip-8-database directory")),
+            "--font" => config.font = Some(flag_value(&mut argv, &arg, "a TTF font file")),
+            "--show-fps" => config.show_fps = true,
+            "--screenshot-dir" => config.screenshot_dir = flag_value(&mut argv, &arg, "a directory"),
+            "--screenshot-scale" => config.screenshot_scale = Some(flag_value(&mut argv, &arg, "a scale factor (1 = 64x32)")),
+            "--record-raw" => config.record_raw = true,
+            _ => rom_path = Some(PathBuf::from(arg)),
+        }
     }
+
+    rom_path
 }
 
-fn draw_screen(chip8: &Emu, canvas: &mut Canvas<Window>, paused: bool, font: &Font) {
-    // Clear canvas as black
-    canvas.set_draw_color(Color::RGB(0, 0, 0));
-    canvas.clear();
+// Bytes per pixel of the streaming screen texture (RGB24)
+const BYTES_PER_PIXEL: usize = 3;
+// Distance of the corner overlays from the window edges, in pixels
+const OVERLAY_MARGIN: i32 = 8;
+
+fn rom_title(rom_path: &Path) -> String {
+    rom_path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+/// Resets the machine and loads `rom` with what the database knows about it.
+/// Quirks chosen by the user win over the ROM's, and ROMs the database
+/// doesn't know get the defaults.
+fn load_rom(chip8: &mut Emu, rom: &[u8], database: &RomDatabase, quirks: Option<Quirks>) -> Option<RomInfo> {
+    chip8.reset();
+    let info = chip8.load_rom_with_info(rom, database);
+    chip8.set_quirks(quirks.or(info.as_ref().and_then(|info| info.quirks)).unwrap_or_default());
+    info
+}
 
+fn window_title(name: &str) -> String {
+    format!("{name} - Chip-8 Emulator")
+}
+
+// The palette the database gives a ROM, offered after the others
+fn rom_palette(info: Option<&RomInfo>) -> Option<Palette> {
+    let colors = info?.colors?;
+    Some(Palette { name: "rom", background: colors.background, foreground: colors.foreground })
+}
+
+/// Where the screen goes in a window of `output` pixels: as large as fits
+/// with the 2:1 aspect ratio kept, centred between black bars. With
+/// `integer` the scale is rounded down to whole pixels, when the window is
+/// large enough for that.
+fn screen_area(output: (u32, u32), integer: bool) -> Rect {
+    let (width, height) = output;
+    let mut scale = (width as f64 / SCREEN_WIDTH as f64).min(height as f64 / SCREEN_HEIGHT as f64);
+    if integer && scale >= 1.0 {
+        scale = scale.floor();
+    }
+    let area_width = ((SCREEN_WIDTH as f64 * scale) as u32).max(1);
+    let area_height = ((SCREEN_HEIGHT as f64 * scale) as u32).max(1);
+    Rect::new(
+        (width as i32 - area_width as i32) / 2,
+        (height as i32 - area_height as i32) / 2,
+        area_width,
+        area_height,
+    )
+}
+
+fn draw_screen(chip8: &Emu, canvas: &mut Canvas<Window>, area: Rect, palette: &Palette, phosphor: &Phosphor, screen_texture: &mut Texture) {
+    // Upload the framebuffer at native resolution, SDL scales it to the window
     let screen = chip8.get_screen();
-    canvas.set_draw_color(Color::RGB(255, 255, 255));
-    for (idx, pixel) in screen.iter().enumerate() {
-        if *pixel {
-            // position of pixel in screen
-            let x = (idx % SCREEN_WIDTH) as i32;
-            let y = (idx / SCREEN_WIDTH) as i32;
-
-            // draw pixel at position (x, y) with scale
-            canvas.fill_rect(Rect::new(x * SCALE as i32, y * SCALE as i32, SCALE, SCALE)).unwrap();
+    screen_texture.with_lock(None, |buffer: &mut [u8], pitch: usize| {
+        for (y, row) in screen.iter().enumerate() {
+            // rows are packed with the leftmost pixel in the most significant bit
+            for x in 0..SCREEN_WIDTH {
+                let lit = row & (1 << (SCREEN_WIDTH - 1 - x)) != 0;
+                let color = palette.mix(phosphor.intensity(x, y, lit));
+                let offset = y * pitch + x * BYTES_PER_PIXEL;
+                buffer[offset..offset + BYTES_PER_PIXEL].copy_from_slice(&color);
+            }
         }
-    }
+    }).unwrap();
+    canvas.set_draw_color(sdl2::pixels::Color::BLACK);
+    canvas.clear();
+    canvas.copy(screen_texture, None, Some(area)).unwrap();
+}
 
-    if paused {
-        let surface = font.render("PAUSE")
-            .blended(Color::RGB(255, 255, 255))
-            .unwrap();
-        let texture_creator = canvas.texture_creator();
-        let texture = texture_creator.create_texture_from_surface(&surface).unwrap();
+/// Draws the text overlays on top of the screen `area` and presents the
+/// frame: `center` (pause, remap prompt), the counter in the top-left corner
+/// and the OSD messages stacked in the bottom-left one.
+fn draw_overlays(canvas: &mut Canvas<Window>, area: Rect, center: Option<&Texture>, counter: Option<&Texture>, osd: &Osd) {
+    if let Some(texture) = center {
         let texture_query = texture.query();
         let rect = Rect::new(
-            (WINDOW_WIDTH as i32 - texture_query.width as i32) / 2,
-            (WINDOW_HEIGHT as i32 - texture_query.height as i32) / 2,
+            area.x() + (area.width() as i32 - texture_query.width as i32) / 2,
+            area.y() + (area.height() as i32 - texture_query.height as i32) / 2,
             texture_query.width,
             texture_query.height,
         );
-        canvas.copy(&texture, None, Some(rect)).unwrap();
+        canvas.copy(texture, None, Some(rect)).unwrap();
+    }
+
+    if let Some(texture) = counter {
+        let texture_query = texture.query();
+        let rect = Rect::new(area.x() + OVERLAY_MARGIN, area.y() + OVERLAY_MARGIN, texture_query.width, texture_query.height);
+        canvas.copy(texture, None, Some(rect)).unwrap();
+    }
+
+    // newest message at the bottom, older ones above it
+    let mut bottom = area.bottom() - OVERLAY_MARGIN;
+    for texture in osd.textures().collect::<Vec<_>>().into_iter().rev() {
+        let texture_query = texture.query();
+        bottom -= texture_query.height as i32;
+        let rect = Rect::new(area.x() + OVERLAY_MARGIN, bottom, texture_query.width, texture_query.height);
+        canvas.copy(texture, None, Some(rect)).unwrap();
+        bottom -= OVERLAY_MARGIN / 2;
     }
 
     // update canvas
@@ -81,12 +188,48 @@ fn draw_screen(chip8: &Emu, canvas: &mut Canvas<Window>, paused: bool, font: &Fo
 }
 
 fn main() {
+    let mut config = config::load();
+    let rom_path = parse_args(&mut config);
+
+    // Validate the settings once config file and flags are merged
+    let palette = config.palette.as_deref().map_or(Ok(PALETTES[0]), palette::parse)
+        .unwrap_or_else(|e| exit_with(format!("palette: {e}")));
+    let quirks = config.quirks.as_deref().map(quirks_or_exit);
+    let opened;
+    let database = match &config.database {
+        Some(dir) => {
+            opened = RomDatabase::open(dir).unwrap_or_else(|e| exit_with(format!("ROM database: {e}")));
+            &opened
+        },
+        None => RomDatabase::bundled(),
+    };
+    if config.scale == 0 || config.screenshot_scale == Some(0) {
+        exit_with("scale must be at least 1");
+    }
+    if config.ipf == Some(0) {
+        exit_with("ipf must be at least 1");
+    }
+    if config.speed.is_nan() || config.speed <= 0.0 {
+        exit_with("speed must be positive");
+    }
+    if !config.pitch.is_finite() || config.pitch <= 0.0 {
+        exit_with("pitch must be a positive frequency in Hz");
+    }
+    if !(0.0..=1.0).contains(&config.volume) {
+        exit_with("volume must be between 0 and 1");
+    }
+    if !(0.0..=MAX_DECAY).contains(&config.persistence) {
+        exit_with(format!("persistence must be between 0 (off) and {MAX_DECAY}"));
+    }
+    let timing = if config.vip_timing { Timing::CosmacVip } else { Timing::Instructions };
+    let cli_ipf = config.ipf.map(|ipf| ipf.min(MAX_IPF));
+
     // try to obtain the path of the ROM file
-    let rom_path = std::env::args().nth(1).map(PathBuf::from).or_else(|| {
+    let rom_path = rom_path.or_else(|| {
         // if the path is not provided, open a file dialog
         FileDialog::new()
             .add_filter("CHIP-8 ROM", &["ch8", "rom", "bin"])
-            .set_directory("../roms") // Carpeta inicial (opcional)
+            .set_directory(&config.rom_dir)
             .pick_file()
     });
 
@@ -99,39 +242,209 @@ fn main() {
     };
 
     let mut chip8 = Emu::new();
+    chip8.set_timing(timing);
+    chip8.set_beep(config.pitch, config.volume);
     let mut rom = File::open(&rom_path).expect("Error opening ROM file");
     let mut buffer = Vec::new();
     rom.read_to_end(&mut buffer).expect("Error reading ROM file");
-    chip8.load_rom(&buffer);
+    let mut info = load_rom(&mut chip8, &buffer, database, quirks);
+    let mut ipf = cli_ipf.or(info.as_ref().and_then(|info| info.ipf)).unwrap_or(DEFAULT_IPF).min(MAX_IPF);
+
+    let mut keymaps = KeymapStore::load(config.keys, config.buttons);
+    let mut rom_hash = core::rom_hash(&buffer);
+    // the file name names screenshots and key profiles, the database title
+    // is what's shown
+    let mut title = rom_title(&rom_path);
+    let mut name = info.as_ref().map_or(title.clone(), |info| info.title.clone());
+    let mut keymap = keymaps.keymap_for(&rom_hash, info.as_ref().map(|info| info.keys));
+    // While remapping: the CHIP-8 key waiting for a binding and the new keymap
+    let mut remap: Option<(usize, keymap::Keymap)> = None;
 
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
-    let window = video_subsystem.window("Chip-8 Emulator", WINDOW_WIDTH, WINDOW_HEIGHT)
-        .position_centered()
-        .opengl()
-        .build()
-        .unwrap();
+    let window_width = SCREEN_WIDTH as u32 * config.scale;
+    let window_height = SCREEN_HEIGHT as u32 * config.scale;
+    let mut window_builder = video_subsystem.window(&window_title(&name), window_width, window_height);
+    window_builder.position_centered().resizable().opengl();
+    if config.fullscreen {
+        window_builder.fullscreen_desktop();
+    }
+    let mut window = window_builder.build().unwrap();
+    window.set_minimum_size(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32).unwrap();
+    sdl_context.mouse().show_cursor(!config.fullscreen);
+
+    // keep pixels sharp when the screen texture is scaled up
+    sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "nearest");
 
-    let mut canvas = window.into_canvas().present_vsync().build().unwrap();
+    // No vsync: emulation speed is driven by our own 60 Hz clock, not the monitor
+    let mut canvas = window.into_canvas().build().unwrap();
     canvas.clear();
     canvas.present();
+    // recomputed whenever the window size changes
+    let mut area = screen_area(canvas.output_size().unwrap(), config.integer_scale);
 
     let mut event_pump = sdl_context.event_pump().unwrap();
+
+    // Game controllers are optional, the keyboard always works
+    let controller_subsystem = sdl_context.game_controller()
+        .map_err(|e| eprintln!("Game controllers unavailable: {e}"))
+        .ok();
+    let mut controllers = Vec::new(); // keeps the open controllers alive
     let mut paused = false; 
+    let mut redraw = true; // something other than the emulated screen changed
+    // the configured speed snaps to the closest of the selectable ones
+    let mut speed_idx = (0..SPEEDS.len())
+        .min_by(|&a, &b| (SPEEDS[a] - config.speed).abs().total_cmp(&(SPEEDS[b] - config.speed).abs()))
+        .unwrap();
+    let mut fast_forward = false;
+
+    let frame_duration = Duration::from_secs(1) / TIMER_HZ;
+    let mut last_update = Instant::now();
+    let mut lag = Duration::ZERO; // emulated time we still owe
 
-    // Pause Font
+    #[cfg(feature = "ttf")]
     let ttf_context = sdl2::ttf::init().unwrap();
-    let font = ttf_context.load_font("/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf", 48).unwrap();
+
+    // Textures are created once and reused every frame
+    let texture_creator = canvas.texture_creator();
+    let mut screen_texture = texture_creator
+        .create_texture_streaming(PixelFormatEnum::RGB24, SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32)
+        .unwrap();
+    // The user-defined palette, if any, joins the cycle after the built-in
+    // ones, then the ROM's own
+    let mut palettes = PALETTES.to_vec();
+    if !palettes.contains(&palette) {
+        palettes.push(palette);
+    }
+    let configured_palettes = palettes.len();
+    let configured_idx = palettes.iter().position(|p| *p == palette).unwrap();
+    palettes.extend(rom_palette(info.as_ref()));
+    let mut palette_idx = if config.palette.is_none() { palettes.len() - 1 } else { configured_idx };
+
+    // Overlay text, at half the size of a CHIP-8 pixel per font pixel; OSD
+    // messages and the counter at a quarter
+    let pixel_size = area.width() / SCREEN_WIDTH as u32;
+    let mut text_renderer = TextRenderer::new(&texture_creator, pixel_size / 2, area.width() * 9 / 10);
+    let mut small_text = TextRenderer::new(&texture_creator, pixel_size / 4, area.width() * 9 / 10);
+    if let Some(path) = &config.font {
+        #[cfg(feature = "ttf")]
+        for renderer in [&mut text_renderer, &mut small_text] {
+            if let Err(e) = renderer.load_ttf(&ttf_context, path) {
+                eprintln!("Error loading font {}, using the built-in one: {e}", path.display());
+                break;
+            }
+        }
+        #[cfg(not(feature = "ttf"))]
+        eprintln!("Built without the ttf feature, ignoring font {}", path.display());
+    }
+    // Overlay text is drawn in the foreground colour, so it is re-rendered
+    // with the palette, and with the window size
+    let remap_prompt = |key: usize| format!("Press key or button for {key:X}");
+    let mut pause_texture = text_renderer.render("PAUSE", palettes[palette_idx].foreground);
+    let mut remap_texture = None;
+
+    let mut osd = Osd::default();
+    let mut show_counter = config.show_fps;
+    let mut counter = Counter::new(chip8.get_instruction_count());
+    let mut counter_texture = None;
+    let mut recording: Option<(Recording, PathBuf)> = None;
+    let quirks_idx_of = |quirks: Quirks| Quirks::PROFILES.iter()
+        .position(|name| Quirks::from_name(name) == Some(quirks))
+        .unwrap_or(0);
+    let mut quirks_idx = quirks_idx_of(chip8.get_quirks());
+
+    let mut phosphor = Phosphor::new(config.persistence);
 
     'gameloop: loop {
         for event in event_pump.poll_iter() {
+            // The remap screen takes every key or button press until all 16
+            // keys are bound. Escape cancels, Backspace keeps the current binding.
+            if let Some((key, new_keymap)) = &mut remap {
+                let mut cancelled = false;
+                let handled = match &event {
+                    Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                        cancelled = true;
+                        true
+                    },
+                    Event::KeyDown { keycode: Some(Keycode::Backspace), .. } => {
+                        *key += 1;
+                        true
+                    },
+                    Event::KeyDown { keycode: Some(keycode), repeat: false, .. } if HOTKEYS.contains(keycode) => {
+                        osd.push(format!("{} is taken, pick another key", keycode.name()), &small_text, palettes[palette_idx].foreground);
+                        true
+                    },
+                    Event::KeyDown { scancode: Some(scancode), repeat: false, .. } => {
+                        new_keymap.bind(*key, *scancode);
+                        *key += 1;
+                        true
+                    },
+                    Event::ControllerButtonDown { button, .. } => {
+                        new_keymap.bind_button(*key, *button);
+                        *key += 1;
+                        true
+                    },
+                    _ => false,
+                };
+
+                if handled {
+                    if !cancelled && *key < NUM_KEYS {
+                        remap_texture = Some(text_renderer.render(&remap_prompt(*key), palettes[palette_idx].foreground));
+                    } else {
+                        let color = palettes[palette_idx].foreground;
+                        if cancelled {
+                            osd.push("Remap cancelled", &small_text, color);
+                        } else {
+                            keymap = new_keymap.clone();
+                            match keymaps.save_profile(&rom_hash, &name, &keymap) {
+                                Ok(()) => osd.push(format!("Saved keys for {name}"), &small_text, color),
+                                Err(e) => {
+                                    eprintln!("Error saving key profile: {e}");
+                                    osd.push("Error saving keys", &small_text, color);
+                                },
+                            }
+                        }
+                        remap = None;
+                        remap_texture = None;
+                    }
+                    redraw = true;
+                    continue;
+                }
+            }
+
             match event {
                 Event::Quit {..} | Event::KeyDown {keycode: Some(Keycode::Escape), ..}=> break 'gameloop,
+                // lay the screen and the overlays out again for the new size
+                Event::Window { win_event: WindowEvent::SizeChanged(..), .. } => {
+                    area = screen_area(canvas.output_size().unwrap(), config.integer_scale);
+                    let pixel_size = area.width() / SCREEN_WIDTH as u32;
+                    text_renderer.resize(pixel_size / 2, area.width() * 9 / 10);
+                    small_text.resize(pixel_size / 4, area.width() * 9 / 10);
+
+                    let color = palettes[palette_idx].foreground;
+                    pause_texture = text_renderer.render("PAUSE", color);
+                    if let Some((key, _)) = &remap {
+                        remap_texture = Some(text_renderer.render(&remap_prompt(*key), color));
+                    }
+                    osd.rerender(&small_text, color);
+                    counter_texture = None; // re-rendered on the next update
+                    redraw = true;
+                },
+                Event::Window { .. } => redraw = true,
+                // toggle fullscreen, at the desktop resolution
+                Event::KeyDown { keycode: Some(Keycode::F11), .. } => {
+                    let fullscreen = canvas.window().fullscreen_state() == FullscreenType::Off;
+                    let mode = if fullscreen { FullscreenType::Desktop } else { FullscreenType::Off };
+                    match canvas.window_mut().set_fullscreen(mode) {
+                        Ok(()) => sdl_context.mouse().show_cursor(!fullscreen),
+                        Err(e) => eprintln!("Error switching fullscreen: {e}"),
+                    }
+                },
                 Event::KeyDown { keycode: Some(Keycode::Return), .. } => {
                     // Open file dialog to select a new ROM when Enter is pressed
                     let new_rom_path = FileDialog::new()
                         .add_filter("CHIP-8 ROM", &["ch8", "rom", "bin"])
-                        .set_directory("../roms")
+                        .set_directory(&config.rom_dir)
                         .pick_file();
 
                     let new_rom_path = match new_rom_path {
@@ -142,21 +455,193 @@ fn main() {
                     rom = File::open(&new_rom_path).expect("Error opening ROM file");
                     buffer = Vec::new();
                     rom.read_to_end(&mut buffer).expect("Error reading ROM file");
-                    chip8.reset();
-                    chip8.load_rom(&buffer);
+                    info = load_rom(&mut chip8, &buffer, database, quirks);
+                    ipf = cli_ipf.or(info.as_ref().and_then(|info| info.ipf)).unwrap_or(DEFAULT_IPF).min(MAX_IPF);
+                    quirks_idx = quirks_idx_of(chip8.get_quirks());
+                    rom_hash = core::rom_hash(&buffer);
+                    title = rom_title(&new_rom_path);
+                    name = info.as_ref().map_or(title.clone(), |info| info.title.clone());
+                    keymap = keymaps.keymap_for(&rom_hash, info.as_ref().map(|info| info.keys));
                     paused = false;
+                    if let Err(e) = canvas.window_mut().set_title(&window_title(&name)) {
+                        eprintln!("Error setting the window title: {e}");
+                    }
+
+                    // swap in the new ROM's palette, or leave the old one's
+                    let previous = palettes[palette_idx];
+                    palettes.truncate(configured_palettes);
+                    palettes.extend(rom_palette(info.as_ref()));
+                    palette_idx = if config.palette.is_none() && palettes.len() > configured_palettes {
+                        palettes.len() - 1
+                    } else if palette_idx >= configured_palettes {
+                        configured_idx
+                    } else {
+                        palette_idx
+                    };
+                    let color = palettes[palette_idx].foreground;
+                    if palettes[palette_idx] != previous {
+                        pause_texture = text_renderer.render("PAUSE", color);
+                        osd.rerender(&small_text, color);
+                        counter_texture = None;
+                    }
+                    osd.push(format!("Loaded {name}"), &small_text, color);
+                    redraw = true;
+                },
+                // instructions per frame
+                Event::KeyDown { keycode: Some(Keycode::Minus), .. } => {
+                    ipf = ipf.saturating_sub(1).max(1);
+                    osd.push(format!("IPF {ipf}"), &small_text, palettes[palette_idx].foreground);
+                    redraw = true;
+                },
+                Event::KeyDown { keycode: Some(Keycode::Equals), .. } => {
+                    ipf = (ipf + 1).min(MAX_IPF);
+                    osd.push(format!("IPF {ipf}"), &small_text, palettes[palette_idx].foreground);
+                    redraw = true;
+                },
+                // emulation speed: slow motion, fast-forward, back to normal
+                Event::KeyDown { keycode: Some(Keycode::LeftBracket | Keycode::RightBracket | Keycode::Backspace), .. } => {
+                    speed_idx = match event {
+                        Event::KeyDown { keycode: Some(Keycode::LeftBracket), .. } => speed_idx.saturating_sub(1),
+                        Event::KeyDown { keycode: Some(Keycode::RightBracket), .. } => (speed_idx + 1).min(SPEEDS.len() - 1),
+                        _ => NORMAL_SPEED,
+                    };
+                    osd.push(format!("Speed {}%", SPEEDS[speed_idx] * 100.0), &small_text, palettes[palette_idx].foreground);
+                    redraw = true;
+                },
+                // cycle through the colour palettes
+                Event::KeyDown { keycode: Some(Keycode::F2), .. } => {
+                    palette_idx = (palette_idx + 1) % palettes.len();
+                    let palette = &palettes[palette_idx];
+                    pause_texture = text_renderer.render("PAUSE", palette.foreground);
+                    osd.rerender(&small_text, palette.foreground);
+                    osd.push(format!("Palette: {}", palette.name), &small_text, palette.foreground);
+                    counter_texture = None; // re-rendered on the next update
+                    redraw = true;
+                },
+                // cycle through the phosphor persistence presets
+                Event::KeyDown { keycode: Some(Keycode::F3), .. } => {
+                    let next = DECAY_PRESETS.iter().position(|&d| d > phosphor.decay()).unwrap_or(0);
+                    phosphor.set_decay(DECAY_PRESETS[next]);
+                    osd.push(format!("Persistence: {}", PRESET_NAMES[next]), &small_text, palettes[palette_idx].foreground);
+                    redraw = true;
+                },
+                // show or hide the FPS/IPS counter
+                Event::KeyDown { keycode: Some(Keycode::F5), .. } => {
+                    show_counter = !show_counter;
+                    counter = Counter::new(chip8.get_instruction_count());
+                    counter_texture = None;
+                    redraw = true;
+                },
+                // save the screen as a PNG in the active palette
+                Event::KeyDown { keycode: Some(Keycode::F12), .. } => {
+                    let palette = &palettes[palette_idx];
+                    let scale = config.screenshot_scale.unwrap_or(config.scale);
+                    let png = chip8.screenshot_png(scale as usize, palette.background, palette.foreground);
+                    let message = match screenshot::save(&config.screenshot_dir, &title, &png) {
+                        Ok(path) => format!("Saved {}", path.file_name().unwrap().to_string_lossy()),
+                        Err(e) => {
+                            eprintln!("Error saving screenshot: {e}");
+                            "Error saving screenshot".to_string()
+                        },
+                    };
+                    osd.push(message, &small_text, palette.foreground);
+                    redraw = true;
+                },
+                // start or stop recording a GIF (and raw video + WAV)
+                Event::KeyDown { keycode: Some(Keycode::F9), .. } => {
+                    let palette = &palettes[palette_idx];
+                    let message = match recording.take() {
+                        Some((finished, path)) => match finished.finish() {
+                            Ok(()) => format!("Saved {}", path.file_name().unwrap().to_string_lossy()),
+                            Err(e) => {
+                                eprintln!("Error saving recording: {e}");
+                                "Error saving recording".to_string()
+                            },
+                        },
+                        None => {
+                            let scale = config.screenshot_scale.unwrap_or(config.scale) as usize;
+                            let started = screenshot::capture_path(&config.screenshot_dir, &title).and_then(|base| {
+                                Recording::create(&base, scale, palette.background, palette.foreground, config.record_raw)
+                                    .map(|r| (r, base.with_added_extension("gif")))
+                                    .map_err(|e| format!("{}: {e}", base.display()))
+                            });
+                            match started {
+                                Ok(started) => {
+                                    recording = Some(started);
+                                    "Recording".to_string()
+                                },
+                                Err(e) => {
+                                    eprintln!("Error starting recording: {e}");
+                                    "Error starting recording".to_string()
+                                },
+                            }
+                        },
+                    };
+                    osd.push(message, &small_text, palette.foreground);
+                    redraw = true;
+                },
+                // cycle through the quirk profiles
+                Event::KeyDown { keycode: Some(Keycode::F6), .. } => {
+                    quirks_idx = (quirks_idx + 1) % Quirks::PROFILES.len();
+                    let name = Quirks::PROFILES[quirks_idx];
+                    chip8.set_quirks(Quirks::from_name(name).unwrap());
+                    osd.push(format!("Quirks: {}", name.to_uppercase()), &small_text, palettes[palette_idx].foreground);
+                    redraw = true;
                 },
+                Event::KeyDown { keycode: Some(Keycode::Tab), repeat: false, .. } => fast_forward = true,
+                Event::KeyUp { keycode: Some(Keycode::Tab), .. } => fast_forward = false,
                 // pause/unpause the emulator with P or space
                 Event::KeyDown { keycode: Some(Keycode::P), .. } | Event::KeyDown { keycode: Some(Keycode::Space), .. } => {
                     paused = !paused;
+                    redraw = true;
+                },
+                // remap the keypad for the current ROM
+                Event::KeyDown { keycode: Some(Keycode::F4), .. } => {
+                    for key in 0..NUM_KEYS {
+                        chip8.keypress(key, false);
+                    }
+                    remap = Some((0, keymap.clone()));
+                    remap_texture = Some(text_renderer.render(&remap_prompt(0), palettes[palette_idx].foreground));
+                    redraw = true;
+                },
+                // scancodes, so the keypad is the same physical block on any layout
+                Event::KeyDown { scancode: Some(scancode), .. } if !paused => {
+                    if let Some(k) = keymap.chip8_key(scancode) {
+                        chip8.keypress(k, true);
+                    }
+                },
+                // releases go through while paused, so no key stays held
+                Event::KeyUp { scancode: Some(scancode), .. } => {
+                    if let Some(k) = keymap.chip8_key(scancode) {
+                        chip8.keypress(k, false);
+                    }
+                },
+                // Controllers are opened when plugged in; SDL also reports the
+                // ones already connected at startup this way.
+                Event::ControllerDeviceAdded { which, .. } => {
+                    if let Some(subsystem) = &controller_subsystem {
+                        match subsystem.open(which) {
+                            Ok(controller) => {
+                                osd.push(format!("Controller connected: {}", controller.name()), &small_text, palettes[palette_idx].foreground);
+                                redraw = true;
+                                controllers.push(controller);
+                            },
+                            Err(e) => eprintln!("Error opening game controller {which}: {e}"),
+                        }
+                    }
+                },
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    controllers.retain(|c| c.instance_id() != which);
+                    osd.push("Controller disconnected", &small_text, palettes[palette_idx].foreground);
+                    redraw = true;
                 },
-                Event::KeyDown { keycode: Some(key), .. } if !paused => {
-                    if let Some(k) = keymap(key) {
+                Event::ControllerButtonDown { button, .. } if !paused => {
+                    if let Some(k) = keymap.chip8_key_for_button(button) {
                         chip8.keypress(k, true);
                     }
                 },
-                Event::KeyUp { keycode: Some(key), .. } if !paused => {
-                    if let Some(k) = keymap(key) {
+                Event::ControllerButtonUp { button, .. } => {
+                    if let Some(k) = keymap.chip8_key_for_button(button) {
                         chip8.keypress(k, false);
                     }
                 },
@@ -164,12 +649,74 @@ fn main() {
             }
         }
         
-        if !paused {
-            for _ in 0..TICKS_PER_FRAME {
-                chip8.tick();
+        // Speed scales how much emulated time passes, never the frame
+        // itself, so timers still tick once per emulated frame.
+        let now = Instant::now();
+        let speed = if fast_forward { FAST_FORWARD } else { SPEEDS[speed_idx] };
+        lag += (now - last_update).mul_f64(speed);
+        last_update = now;
+
+        if paused || remap.is_some() {
+            lag = Duration::ZERO;
+        } else {
+            // don't try to catch up forever if we fell far behind
+            lag = lag.min(frame_duration * MAX_FRAMES_PER_UPDATE);
+            while lag >= frame_duration {
+                chip8.run_frame(ipf);
+                if let Some((active, _)) = &mut recording && let Err(e) = active.frame(&chip8) {
+                    eprintln!("Error recording, stopped: {e}");
+                    osd.push("Recording stopped", &small_text, palettes[palette_idx].foreground);
+                    recording = None;
+                }
+                // keep redrawing while pixels are fading out
+                redraw |= phosphor.update(chip8.get_screen());
+                lag -= frame_duration;
             }
-            chip8.tick_timers();
         }
-        draw_screen(&chip8, &mut canvas, paused, &font);
+        redraw |= osd.expire(now);
+        if show_counter && let Some(text) = counter.update(now, chip8.get_instruction_count()) {
+            counter_texture = Some(small_text.render(&text, palettes[palette_idx].foreground));
+            redraw = true;
+        }
+
+        // only redraw when the emulated screen or the overlay changed
+        if chip8.take_dirty().is_some() || redraw {
+            draw_screen(&chip8, &mut canvas, area, &palettes[palette_idx], &phosphor, &mut screen_texture);
+            let center = remap_texture.as_ref().or(paused.then_some(&pause_texture));
+            draw_overlays(&mut canvas, area, center, counter_texture.as_ref().filter(|_| show_counter), &osd);
+            counter.presented();
+            redraw = false;
+        }
+
+        // sleep until the next frame is due
+        std::thread::sleep(frame_duration.saturating_sub(now.elapsed()));
+    }
+
+    if let Some((recording, _)) = recording && let Err(e) = recording.finish() {
+        eprintln!("Error saving recording: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn screen_area_keeps_the_aspect_ratio() {
+        // a 2:1 window is filled
+        assert_eq!(screen_area((640, 320), false), Rect::new(0, 0, 640, 320));
+        // bars above and below a taller one, left and right of a wider one
+        assert_eq!(screen_area((800, 600), false), Rect::new(0, 100, 800, 400));
+        assert_eq!(screen_area((1000, 300), false), Rect::new(200, 0, 600, 300));
+    }
+
+    #[test]
+    fn screen_area_rounds_to_whole_pixels() {
+        // 12.5 rounds down to 12 screen pixels per CHIP-8 pixel
+        assert_eq!(screen_area((800, 600), true), Rect::new(16, 108, 768, 384));
+        // smaller than 1:1, so the fractional scale stays
+        assert_eq!(screen_area((32, 16), true), Rect::new(0, 0, 32, 16));
+        // never empty, even in a minimised window
+        assert_eq!(screen_area((0, 0), true), Rect::new(0, 0, 1, 1));
     }
 }
@@ -0,0 +1,82 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use sdl2::render::Texture;
+
+use crate::text::TextRenderer;
+
+const MESSAGE_DURATION: Duration = Duration::from_secs(2);
+const MAX_MESSAGES: usize = 4;
+const COUNTER_PERIOD: Duration = Duration::from_secs(1);
+
+/// Transient status messages ("Speed 200%", "Quirks: SCHIP"...) shown in the
+/// bottom-left corner, newest last. Each one disappears after a while, or
+/// earlier when too many pile up.
+#[derive(Default)]
+pub struct Osd<'t> {
+    messages: VecDeque<(String, Texture<'t>, Instant)>,
+}
+
+impl<'t> Osd<'t> {
+    pub fn push(&mut self, text: impl Into<String>, renderer: &TextRenderer<'t>, color: [u8; 3]) {
+        let text = text.into();
+        if self.messages.len() == MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+        let texture = renderer.render(&text, color);
+        self.messages.push_back((text, texture, Instant::now()));
+    }
+
+    /// Drops the messages that have been shown long enough. Returns true if
+    /// any were removed, so the screen needs a redraw.
+    pub fn expire(&mut self, now: Instant) -> bool {
+        let count = self.messages.len();
+        self.messages.retain(|(_, _, shown)| now - *shown < MESSAGE_DURATION);
+        self.messages.len() != count
+    }
+
+    /// Renders the messages again, e.g. after a palette change or a resize.
+    pub fn rerender(&mut self, renderer: &TextRenderer<'t>, color: [u8; 3]) {
+        for (text, texture, _) in &mut self.messages {
+            *texture = renderer.render(text, color);
+        }
+    }
+
+    pub fn textures(&self) -> impl Iterator<Item = &Texture<'t>> {
+        self.messages.iter().map(|(_, texture, _)| texture)
+    }
+}
+
+/// Measures frames drawn and instructions executed per second, averaged over
+/// one second. Only frames that reach the window count, so a still screen,
+/// which isn't redrawn, shows a low FPS while the game runs at full speed.
+pub struct Counter {
+    started: Instant,
+    frames: u32,
+    instructions: u64, // instruction count at the start of the period
+}
+
+impl Counter {
+    pub fn new(instructions: u64) -> Self {
+        Self { started: Instant::now(), frames: 0, instructions }
+    }
+
+    pub fn presented(&mut self) {
+        self.frames += 1;
+    }
+
+    /// Once per period, returns the counter text and starts a new period.
+    /// `instructions` is the emulator's current instruction count.
+    pub fn update(&mut self, now: Instant, instructions: u64) -> Option<String> {
+        let elapsed = now - self.started;
+        if elapsed < COUNTER_PERIOD {
+            return None;
+        }
+        let seconds = elapsed.as_secs_f64();
+        let fps = self.frames as f64 / seconds;
+        // the count restarts when a ROM is loaded
+        let ips = instructions.saturating_sub(self.instructions) as f64 / seconds;
+        *self = Self::new(instructions);
+        Some(format!("{fps:.0} FPS {ips:.0} IPS"))
+    }
+}
@@ -0,0 +1,73 @@
+/// Background and foreground colours of the display, as RGB.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    pub name: &'static str,
+    pub background: [u8; 3],
+    pub foreground: [u8; 3],
+}
+
+impl Palette {
+    /// Colour of a pixel with the given brightness, from background (0) to
+    /// foreground (1).
+    pub fn mix(&self, intensity: f32) -> [u8; 3] {
+        let mut color = [0; 3];
+        for (c, (&bg, &fg)) in color.iter_mut().zip(self.background.iter().zip(&self.foreground)) {
+            *c = (bg as f32 + (fg as f32 - bg as f32) * intensity).round() as u8;
+        }
+        color
+    }
+}
+
+pub const PALETTES: [Palette; 4] = [
+    Palette { name: "classic", background: [0x00, 0x00, 0x00], foreground: [0xFF, 0xFF, 0xFF] },
+    Palette { name: "amber", background: [0x1A, 0x0E, 0x00], foreground: [0xFF, 0xB0, 0x00] },
+    Palette { name: "green", background: [0x00, 0x14, 0x00], foreground: [0x33, 0xFF, 0x33] },
+    Palette { name: "lcd", background: [0x9B, 0xBC, 0x0F], foreground: [0x0F, 0x38, 0x0F] },
+];
+
+/// Parses either the name of a built-in palette or a user-defined one given
+/// as two hex colours, `BACKGROUND:FOREGROUND` (e.g. `202020:e0e0e0`).
+pub fn parse(spec: &str) -> Result<Palette, String> {
+    if let Some(palette) = PALETTES.iter().find(|p| p.name.eq_ignore_ascii_case(spec)) {
+        return Ok(*palette);
+    }
+
+    let (background, foreground) = spec.split_once(':')
+        .ok_or_else(|| format!("unknown palette '{spec}'"))?;
+    Ok(Palette {
+        name: "custom",
+        background: parse_rgb(background)?,
+        foreground: parse_rgb(foreground)?,
+    })
+}
+
+fn parse_rgb(hex: &str) -> Result<[u8; 3], String> {
+    let hex = hex.trim_start_matches('#');
+    // from_str_radix alone would take a sign
+    let value = u32::from_str_radix(hex, 16).ok().filter(|_| hex.len() == 6 && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+        .ok_or_else(|| format!("'{hex}' is not a RRGGBB colour"))?;
+    let [_, r, g, b] = value.to_be_bytes();
+    Ok([r, g, b])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_names_and_hex_pairs() {
+        assert_eq!(parse("Amber"), Ok(PALETTES[1]));
+        let custom = parse("#202020:E0e0e0").unwrap();
+        assert_eq!((custom.background, custom.foreground), ([0x20; 3], [0xE0; 3]));
+    }
+
+    #[test]
+    fn rejects_bad_palettes() {
+        assert_eq!(parse("purple"), Err("unknown palette 'purple'".to_string()));
+        // too short, too long, not hex, a sign
+        for colour in ["12345", "1234567", "12345g", "+12345", ""] {
+            assert_eq!(parse(&format!("000000:{colour}")), Err(format!("'{colour}' is not a RRGGBB colour")));
+        }
+        assert!(parse("000000:ffffff:000000").is_err());
+    }
+}
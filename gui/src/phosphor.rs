@@ -0,0 +1,66 @@
+use core::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// Below this a fading pixel is considered off
+const CUTOFF: f32 = 1.0 / 64.0;
+pub const MAX_DECAY: f32 = 0.95;
+
+// Decay levels cycled with F3
+pub const DECAY_PRESETS: [f32; 4] = [0.0, 0.5, 0.7, 0.85];
+pub const PRESET_NAMES: [&str; 4] = ["off", "light", "medium", "heavy"];
+
+/// Software persistence filter that fades pixels out over a few frames, like
+/// the phosphor of a CRT, to hide the flicker of XOR-erased sprites.
+pub struct Phosphor {
+    decay: f32, // brightness kept per frame, 0 disables the filter
+    levels: Vec<f32>,
+}
+
+impl Phosphor {
+    pub fn new(decay: f32) -> Self {
+        Self { decay: decay.clamp(0.0, MAX_DECAY), levels: vec![0.0; SCREEN_WIDTH * SCREEN_HEIGHT] }
+    }
+
+    pub fn decay(&self) -> f32 {
+        self.decay
+    }
+
+    pub fn set_decay(&mut self, decay: f32) {
+        self.decay = decay.clamp(0.0, MAX_DECAY);
+        if self.decay == 0.0 {
+            self.levels.fill(0.0); // no stale afterglow if it is turned back on
+        }
+    }
+
+    /// Advances the filter by one emulated frame. Returns true while some
+    /// pixel is still fading, meaning the display must be redrawn.
+    pub fn update(&mut self, screen: &[u64]) -> bool {
+        if self.decay == 0.0 {
+            return false;
+        }
+
+        let mut fading = false;
+        for (y, row) in screen.iter().enumerate() {
+            for x in 0..SCREEN_WIDTH {
+                let level = &mut self.levels[y * SCREEN_WIDTH + x];
+                if row & (1 << (SCREEN_WIDTH - 1 - x)) != 0 {
+                    *level = 1.0;
+                } else if *level > 0.0 {
+                    *level *= self.decay;
+                    if *level < CUTOFF {
+                        *level = 0.0;
+                    }
+                    fading = true;
+                }
+            }
+        }
+        fading
+    }
+
+    /// Brightness of the pixel at (x, y) between 0 and 1.
+    pub fn intensity(&self, x: usize, y: usize, lit: bool) -> f32 {
+        if lit || self.decay == 0.0 {
+            return if lit { 1.0 } else { 0.0 };
+        }
+        self.levels[y * SCREEN_WIDTH + x]
+    }
+}
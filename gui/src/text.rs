@@ -0,0 +1,214 @@
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::{BlendMode, Texture, TextureCreator};
+use sdl2::video::WindowContext;
+
+#[cfg(feature = "ttf")]
+use std::path::{Path, PathBuf};
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+// glyph plus one column of spacing
+const ADVANCE: usize = GLYPH_WIDTH + 1;
+const FIRST_GLYPH: char = ' ';
+
+// 5x7 font for printable ASCII, one byte per row with the leftmost pixel in
+// bit 4. Anything outside the range is drawn as '?'.
+const GLYPHS: [[u8; GLYPH_HEIGHT]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // space
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04], // !
+    [0x0A, 0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00], // "
+    [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A], // #
+    [0x04, 0x0F, 0x14, 0x0E, 0x05, 0x1E, 0x04], // $
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03], // %
+    [0x0C, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0D], // &
+    [0x0C, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00], // '
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02], // (
+    [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08], // )
+    [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00], // *
+    [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00], // +
+    [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08], // ,
+    [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00], // -
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C], // .
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00], // /
+    [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E], // 0
+    [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E], // 1
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F], // 2
+    [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E], // 3
+    [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02], // 4
+    [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E], // 5
+    [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E], // 6
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // 7
+    [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E], // 8
+    [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C], // 9
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00], // :
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08], // ;
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02], // <
+    [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00], // =
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08], // >
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04], // ?
+    [0x0E, 0x11, 0x01, 0x0D, 0x15, 0x15, 0x0E], // @
+    [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // A
+    [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E], // B
+    [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E], // C
+    [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C], // D
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F], // E
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10], // F
+    [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F], // G
+    [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // H
+    [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E], // I
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C], // J
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // K
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F], // L
+    [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11], // M
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // N
+    [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // O
+    [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10], // P
+    [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D], // Q
+    [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11], // R
+    [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E], // S
+    [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // T
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // U
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04], // V
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A], // W
+    [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11], // X
+    [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04], // Y
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F], // Z
+    [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E], // [
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00], // backslash
+    [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E], // ]
+    [0x04, 0x0A, 0x11, 0x00, 0x00, 0x00, 0x00], // ^
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F], // _
+    [0x08, 0x04, 0x02, 0x00, 0x00, 0x00, 0x00], // `
+    [0x00, 0x00, 0x0E, 0x01, 0x0F, 0x11, 0x0F], // a
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x1E], // b
+    [0x00, 0x00, 0x0E, 0x10, 0x10, 0x11, 0x0E], // c
+    [0x01, 0x01, 0x0D, 0x13, 0x11, 0x11, 0x0F], // d
+    [0x00, 0x00, 0x0E, 0x11, 0x1F, 0x10, 0x0E], // e
+    [0x06, 0x09, 0x08, 0x1C, 0x08, 0x08, 0x08], // f
+    [0x00, 0x0F, 0x11, 0x11, 0x0F, 0x01, 0x0E], // g
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x11], // h
+    [0x04, 0x00, 0x0C, 0x04, 0x04, 0x04, 0x0E], // i
+    [0x02, 0x00, 0x06, 0x02, 0x02, 0x12, 0x0C], // j
+    [0x10, 0x10, 0x12, 0x14, 0x18, 0x14, 0x12], // k
+    [0x0C, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E], // l
+    [0x00, 0x00, 0x1A, 0x15, 0x15, 0x11, 0x11], // m
+    [0x00, 0x00, 0x16, 0x19, 0x11, 0x11, 0x11], // n
+    [0x00, 0x00, 0x0E, 0x11, 0x11, 0x11, 0x0E], // o
+    [0x00, 0x00, 0x1E, 0x11, 0x1E, 0x10, 0x10], // p
+    [0x00, 0x00, 0x0D, 0x13, 0x0F, 0x01, 0x01], // q
+    [0x00, 0x00, 0x16, 0x19, 0x10, 0x10, 0x10], // r
+    [0x00, 0x00, 0x0E, 0x10, 0x0E, 0x01, 0x1E], // s
+    [0x08, 0x08, 0x1C, 0x08, 0x08, 0x09, 0x06], // t
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x13, 0x0D], // u
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x0A, 0x04], // v
+    [0x00, 0x00, 0x11, 0x11, 0x15, 0x15, 0x0A], // w
+    [0x00, 0x00, 0x11, 0x0A, 0x04, 0x0A, 0x11], // x
+    [0x00, 0x00, 0x11, 0x11, 0x0F, 0x01, 0x0E], // y
+    [0x00, 0x00, 0x1F, 0x02, 0x04, 0x08, 0x1F], // z
+    [0x02, 0x04, 0x04, 0x08, 0x04, 0x04, 0x02], // {
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // |
+    [0x08, 0x04, 0x04, 0x02, 0x04, 0x04, 0x08], // }
+    [0x00, 0x00, 0x08, 0x15, 0x02, 0x00, 0x00], // ~
+];
+
+fn glyph(c: char) -> &'static [u8; GLYPH_HEIGHT] {
+    let index = (c as usize).wrapping_sub(FIRST_GLYPH as usize);
+    GLYPHS.get(index).unwrap_or(&GLYPHS['?' as usize - FIRST_GLYPH as usize])
+}
+
+/// Draws `text` with the built-in font into an RGBA buffer with a
+/// transparent background. Returns the width, height and pixels.
+fn rasterize(text: &str, color: [u8; 3], scale: usize) -> (usize, usize, Vec<u8>) {
+    let chars = text.chars().count();
+    let width = (chars * ADVANCE).saturating_sub(1).max(1) * scale;
+    let height = GLYPH_HEIGHT * scale;
+    let mut pixels = vec![0; width * height * 4];
+
+    for (i, c) in text.chars().enumerate() {
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - col)) == 0 {
+                    continue;
+                }
+                // each font pixel becomes a scale x scale block
+                for y in row * scale..(row + 1) * scale {
+                    let x = (i * ADVANCE + col) * scale;
+                    for offset in (y * width + x..y * width + x + scale).map(|p| p * 4) {
+                        let [r, g, b] = color;
+                        pixels[offset..offset + 4].copy_from_slice(&[r, g, b, 0xFF]);
+                    }
+                }
+            }
+        }
+    }
+    (width, height, pixels)
+}
+
+/// Turns overlay text (pause screen, prompts, messages) into textures. Uses
+/// the built-in bitmap font, or a TTF font when built with the `ttf` feature
+/// and one is configured.
+pub struct TextRenderer<'t> {
+    texture_creator: &'t TextureCreator<WindowContext>,
+    scale: usize,     // size of a font pixel in window pixels
+    max_width: usize, // text is shrunk to fit in this width
+    // the font and where it came from, to load it again at another size
+    #[cfg(feature = "ttf")]
+    ttf: Option<(&'t sdl2::ttf::Sdl2TtfContext, PathBuf, sdl2::ttf::Font<'t, 'static>)>,
+}
+
+impl<'t> TextRenderer<'t> {
+    pub fn new(texture_creator: &'t TextureCreator<WindowContext>, scale: u32, max_width: u32) -> Self {
+        Self {
+            texture_creator,
+            scale: (scale as usize).max(1),
+            max_width: max_width as usize,
+            #[cfg(feature = "ttf")]
+            ttf: None,
+        }
+    }
+
+    /// Uses a TTF font instead of the built-in one, about as tall as the
+    /// bitmap text would be.
+    #[cfg(feature = "ttf")]
+    pub fn load_ttf(&mut self, ttf_context: &'t sdl2::ttf::Sdl2TtfContext, path: &Path) -> Result<(), String> {
+        let point_size = (GLYPH_HEIGHT * self.scale) as u16;
+        let font = ttf_context.load_font(path, point_size)?;
+        self.ttf = Some((ttf_context, path.to_path_buf(), font));
+        Ok(())
+    }
+
+    /// Changes the text size, e.g. after the window was resized. Textures
+    /// rendered before keep their size.
+    pub fn resize(&mut self, scale: u32, max_width: u32) {
+        self.scale = (scale as usize).max(1);
+        self.max_width = max_width as usize;
+
+        #[cfg(feature = "ttf")]
+        if let Some((ttf_context, path, font)) = &mut self.ttf {
+            match ttf_context.load_font(path.as_path(), (GLYPH_HEIGHT * self.scale) as u16) {
+                Ok(resized) => *font = resized,
+                Err(e) => eprintln!("Error resizing font {}: {e}", path.display()),
+            }
+        }
+    }
+
+    pub fn render(&self, text: &str, color: [u8; 3]) -> Texture<'t> {
+        #[cfg(feature = "ttf")]
+        if let Some((_, _, font)) = &self.ttf {
+            let [r, g, b] = color;
+            let surface = font.render(text).blended(sdl2::pixels::Color::RGB(r, g, b)).unwrap();
+            return self.texture_creator.create_texture_from_surface(&surface).unwrap();
+        }
+
+        let unscaled_width = (text.chars().count() * ADVANCE).max(1);
+        let scale = self.scale.min(self.max_width / unscaled_width).max(1);
+        let (width, height, pixels) = rasterize(text, color, scale);
+
+        let mut texture = self.texture_creator
+            .create_texture_static(PixelFormatEnum::RGBA32, width as u32, height as u32)
+            .unwrap();
+        texture.update(None, &pixels, width * 4).unwrap();
+        texture.set_blend_mode(BlendMode::Blend);
+        texture
+    }
+}
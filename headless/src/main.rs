@@ -0,0 +1,158 @@
+// Runs a ROM without a window, as fast as possible, for a fixed number of
+// frames. Useful for recording demos and for scripted runs.
+
+use std::path::PathBuf;
+
+use core::*;
+use core::cli::{exit_with, flag_value, quirks_or_exit};
+
+const DEFAULT_FRAMES: u64 = 600; // 10 seconds
+const DEFAULT_IPF: usize = 10;
+const DEFAULT_SCALE: usize = 4;
+const BACKGROUND: [u8; 3] = [0x00, 0x00, 0x00];
+const FOREGROUND: [u8; 3] = [0xFF, 0xFF, 0xFF];
+
+struct Args {
+    rom_path: PathBuf,
+    frames: u64,
+    ipf: usize,
+    quirks: Quirks,
+    timing: Timing,
+    record: Option<PathBuf>, // base path, extensions are added
+    raw: bool,
+    scale: usize,
+    analyze: bool, // print what the ROM's code uses instead of running it
+}
+
+fn parse_args() -> Args {
+    let mut rom_path = None;
+    let mut args = Args {
+        rom_path: PathBuf::new(),
+        frames: DEFAULT_FRAMES,
+        ipf: DEFAULT_IPF,
+        quirks: Quirks::default(),
+        timing: Timing::Instructions,
+        record: None,
+        raw: false,
+        scale: DEFAULT_SCALE,
+        analyze: false,
+    };
+    let mut argv = std::env::args().skip(1);
+
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "--frames" => args.frames = flag_value(&mut argv, &arg, "a number of frames"),
+            "--ipf" => args.ipf = flag_value(&mut argv, &arg, "a number of instructions per frame"),
+            "--quirks" => args.quirks = quirks_or_exit(&flag_value::<String>(&mut argv, &arg, "a quirk profile")),
+            "--vip-timing" => args.timing = Timing::CosmacVip,
+            "--record" => args.record = Some(flag_value(&mut argv, &arg, "an output path without extension")),
+            "--raw" => args.raw = true,
+            "--scale" => args.scale = flag_value(&mut argv, &arg, "a scale factor (1 = 64x32)"),
+            "--analyze" => args.analyze = true,
+            _ => rom_path = Some(PathBuf::from(arg)),
+        }
+    }
+
+    args.rom_path = rom_path.unwrap_or_else(|| {
+        exit_with("usage: headless [--frames N] [--ipf N] [--quirks PROFILE] [--vip-timing] [--record BASE [--raw] [--scale N]] [--analyze] ROM")
+    });
+    if args.ipf == 0 || args.scale == 0 {
+        exit_with("ipf and scale must be at least 1");
+    }
+    args
+}
+
+fn main() {
+    let args = parse_args();
+    let rom = std::fs::read(&args.rom_path)
+        .unwrap_or_else(|e| exit_with(format!("Error reading {}: {e}", args.rom_path.display())));
+    if args.analyze {
+        print_analysis(&rom);
+        return;
+    }
+
+    let mut chip8 = HeadlessEmu::headless();
+    chip8.set_quirks(args.quirks);
+    chip8.set_timing(args.timing);
+    chip8.load_rom(&rom);
+    let game = find_game(&rom_hash(&rom));
+    chip8.set_game_rules(game.map(|game| game.rules));
+
+    let mut recording = args.record.as_ref().map(|base| {
+        Recording::create(base, args.scale, BACKGROUND, FOREGROUND, args.raw)
+            .unwrap_or_else(|e| exit_with(format!("Error creating {}: {e}", base.display())))
+    });
+
+    for _ in 0..args.frames {
+        chip8.run_frame(args.ipf);
+        if let Some(recording) = &mut recording {
+            recording.frame(&chip8).unwrap_or_else(|e| exit_with(format!("Error recording: {e}")));
+        }
+    }
+
+    if let Some(recording) = recording {
+        recording.finish().unwrap_or_else(|e| exit_with(format!("Error recording: {e}")));
+    }
+    println!("Ran {} frames, {} instructions", args.frames, chip8.get_instruction_count());
+    if let (Some(game), Some(status)) = (game, chip8.game_status()) {
+        print_status(&game.title, status);
+    }
+}
+
+fn print_status(title: &str, status: GameStatus) {
+    let mut line = title.to_string();
+    if let Some(score) = status.score {
+        line += &format!(", score {score}");
+    }
+    if let Some(lives) = status.lives {
+        line += &format!(", {lives} lives left");
+    }
+    if status.game_over {
+        line += ", game over";
+    } else if status.won {
+        line += ", won";
+    }
+    println!("{line}");
+}
+
+fn print_analysis(rom: &[u8]) {
+    let analysis = analyze_rom(rom);
+    let platform = analysis.platform();
+    let supported = if platform.is_supported() { "" } else { ", not supported by this emulator" };
+    println!("Platform: {} ({}{supported})", platform.name(), platform.database_id());
+
+    let quirks = analysis.quirks();
+    let names = [
+        (quirks.vf_reset, "vf_reset"),
+        (quirks.memory_increment, "memory_increment"),
+        (quirks.memory_increment && quirks.memory_increment_by_x, "memory_increment_by_x"),
+        (quirks.shift_uses_vy, "shift_uses_vy"),
+        (quirks.jump_uses_vx, "jump_uses_vx"),
+        (quirks.clip_sprites, "clip_sprites"),
+    ];
+    let on: Vec<_> = names.iter().filter(|(set, _)| *set).map(|(_, name)| *name).collect();
+    println!("Quirks: {}", if on.is_empty() { "none".to_string() } else { on.join(", ") });
+    println!("Code: {} of {} bytes", analysis.code_bytes, rom.len());
+
+    let findings = [
+        (analysis.schip, "SUPER-CHIP instructions"),
+        (analysis.xochip, "XO-CHIP instructions"),
+        (analysis.machine_code, "machine code calls (0NNN)"),
+        (analysis.self_modifying, "instructions overwritten by the program"),
+        (analysis.shifts_vy, "shifts of VY into VX (8XY6/8XYE)"),
+        (analysis.shifts_in_place, "shifts of VX in place (8X06/8X0E)"),
+        (analysis.memory_loops, "uses of I moved by FX55/FX65"),
+        (analysis.load_then_store, "stores of what FX65 loaded (FX65, FX55)"),
+        (analysis.jumps_vx, "BNNN jumps with X other than 0"),
+        (analysis.unknown, "unknown opcodes"),
+    ];
+    for (finding, what) in findings {
+        if let Some(first) = finding.first {
+            println!("  {} {what}, first at {first:#05X}", finding.count);
+        }
+    }
+
+    if let Some(info) = RomDatabase::bundled().lookup(&rom_hash(rom)) {
+        println!("Known ROM: {} ({})", info.title, info.platforms.join(", "));
+    }
+}
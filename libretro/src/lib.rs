@@ -0,0 +1,422 @@
+// libretro core, for RetroArch and other libretro frontends. The frontend
+// drives everything: it calls `retro_run` once per 60 Hz frame, and we send
+// back one video frame and a frame's worth of audio.
+
+// The exported functions follow the contracts in libretro.h
+#![allow(clippy::missing_safety_doc)]
+
+use std::ffi::{c_uint, c_void, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{LazyLock, Mutex, MutexGuard};
+
+use chip8::{BeepSynth, Emu, Quirks, Timing, MAX_ROM_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH, STATE_SIZE};
+
+mod sys;
+#[cfg(test)]
+mod tests;
+
+use sys::*;
+
+const FPS: f64 = 60.0;
+const SAMPLE_RATE: u32 = 44100;
+const SAMPLES_PER_FRAME: usize = SAMPLE_RATE as usize / FPS as usize;
+const FOREGROUND: u32 = 0xFFFFFF;
+const BACKGROUND: u32 = 0x000000;
+const DEFAULT_IPF: usize = 10;
+const MESSAGE_FRAMES: c_uint = 600; // 10 s
+
+// The D-pad and A as in the GUI's default layout, the other buttons take
+// the remaining keys so every game is playable
+const BUTTONS: [(c_uint, usize); 16] = [
+    (DEVICE_ID_JOYPAD_UP, 0x2),
+    (DEVICE_ID_JOYPAD_DOWN, 0x8),
+    (DEVICE_ID_JOYPAD_LEFT, 0x4),
+    (DEVICE_ID_JOYPAD_RIGHT, 0x6),
+    (DEVICE_ID_JOYPAD_A, 0x5),
+    (DEVICE_ID_JOYPAD_B, 0x0),
+    (DEVICE_ID_JOYPAD_X, 0x9),
+    (DEVICE_ID_JOYPAD_Y, 0x7),
+    (DEVICE_ID_JOYPAD_L, 0x1),
+    (DEVICE_ID_JOYPAD_R, 0x3),
+    (DEVICE_ID_JOYPAD_L2, 0xA),
+    (DEVICE_ID_JOYPAD_R2, 0xB),
+    (DEVICE_ID_JOYPAD_L3, 0xC),
+    (DEVICE_ID_JOYPAD_R3, 0xD),
+    (DEVICE_ID_JOYPAD_SELECT, 0xE),
+    (DEVICE_ID_JOYPAD_START, 0xF),
+];
+
+const IPF_OPTION: &CStr = c"chip8_ipf";
+const TIMING_OPTION: &CStr = c"chip8_timing";
+const QUIRKS_OPTION: &CStr = c"chip8_quirks";
+const VIP_TIMING: &str = "COSMAC VIP";
+
+// Core options as (key, "Description; default|other|..."). The frontend
+// keeps the pointers, so they live for the whole program.
+static OPTIONS: LazyLock<Vec<(&CStr, CString)>> = LazyLock::new(|| {
+    let option = |text: String| CString::new(text).unwrap();
+    vec![
+        (IPF_OPTION, option(format!("Speed (instructions per frame); {DEFAULT_IPF}|15|20|30|50|100|200|500|1000|1|2|3|5|7"))),
+        (TIMING_OPTION, option(format!("Timing; instructions per frame|{VIP_TIMING}"))),
+        (QUIRKS_OPTION, option(format!("Quirk profile; {}", Quirks::PROFILES.join("|")))),
+    ]
+});
+
+static BUTTON_NAMES: LazyLock<Vec<CString>> = LazyLock::new(|| {
+    BUTTONS.iter().map(|(_, key)| CString::new(format!("Key {key:X}")).unwrap()).collect()
+});
+
+#[derive(Clone, Copy)]
+struct Callbacks {
+    environment: Option<EnvironmentFn>,
+    video_refresh: Option<VideoRefreshFn>,
+    audio_sample_batch: Option<AudioSampleBatchFn>,
+    input_poll: Option<InputPollFn>,
+    input_state: Option<InputStateFn>,
+}
+
+static CALLBACKS: Mutex<Callbacks> = Mutex::new(Callbacks {
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+});
+
+// The loaded game, None between retro_unload_game and the next load
+static GAME: Mutex<Option<Game>> = Mutex::new(None);
+
+struct Game {
+    emu: Emu,
+    rom: Vec<u8>, // for retro_reset
+    ipf: usize,
+    pixels: Vec<u8>, // one byte per pixel, as Framebuffer::write_bytes writes them
+    video: Vec<u32>, // XRGB8888
+    synth: BeepSynth,
+    crashed: bool, // stopped on an instruction it can't run, until a reset or a state
+}
+
+impl Game {
+    fn apply_options(&mut self, options: Options) {
+        self.ipf = options.ipf;
+        if options.timing != self.emu.get_timing() {
+            self.emu.set_timing(options.timing);
+        }
+        self.emu.set_quirks(options.quirks);
+    }
+
+    fn restart(&mut self) {
+        self.emu.reset();
+        self.emu.load_rom(&self.rom);
+        self.crashed = false;
+    }
+
+    // Runs a frame unless the game crashed, and returns what went wrong if
+    // it crashes now. A panic can't unwind into the frontend.
+    fn run_frame(&mut self) -> Option<String> {
+        if self.crashed {
+            return None;
+        }
+        let (emu, ipf) = (&mut self.emu, self.ipf);
+        let error = match catch_unwind(AssertUnwindSafe(|| emu.try_run_frame(ipf))) {
+            Ok(Ok(())) => return None,
+            Ok(Err(fault)) => fault.to_string(),
+            Err(_) => "the emulator crashed".to_string(),
+        };
+        self.crashed = true;
+        Some(error)
+    }
+}
+
+// The core options' values, read before locking the game since reading them
+// calls the frontend
+struct Options {
+    ipf: usize,
+    timing: Timing,
+    quirks: Quirks,
+}
+
+fn options() -> Options {
+    Options {
+        ipf: variable(IPF_OPTION).and_then(|ipf| ipf.parse().ok()).unwrap_or(DEFAULT_IPF),
+        timing: match variable(TIMING_OPTION).as_deref() {
+            Some(VIP_TIMING) => Timing::CosmacVip,
+            _ => Timing::Instructions,
+        },
+        quirks: variable(QUIRKS_OPTION).and_then(|name| Quirks::from_name(&name)).unwrap_or_default(),
+    }
+}
+
+fn callbacks() -> Callbacks {
+    *CALLBACKS.lock().unwrap()
+}
+
+fn game() -> MutexGuard<'static, Option<Game>> {
+    GAME.lock().unwrap()
+}
+
+fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    match callbacks().environment {
+        Some(environment) => unsafe { environment(cmd, data) },
+        None => false,
+    }
+}
+
+fn variable(key: &CStr) -> Option<String> {
+    let mut variable = Variable { key: key.as_ptr(), value: std::ptr::null() };
+    if !environment(ENVIRONMENT_GET_VARIABLE, &mut variable as *mut Variable as *mut c_void) || variable.value.is_null() {
+        return None;
+    }
+    Some(unsafe { CStr::from_ptr(variable.value) }.to_string_lossy().into_owned())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_api_version() -> c_uint {
+    API_VERSION
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_environment(callback: EnvironmentFn) {
+    CALLBACKS.lock().unwrap().environment = Some(callback);
+
+    let mut variables: Vec<Variable> = OPTIONS
+        .iter()
+        .map(|(key, value)| Variable { key: key.as_ptr(), value: value.as_ptr() })
+        .collect();
+    variables.push(Variable { key: std::ptr::null(), value: std::ptr::null() });
+    environment(ENVIRONMENT_SET_VARIABLES, variables.as_mut_ptr() as *mut c_void);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_video_refresh(callback: VideoRefreshFn) {
+    CALLBACKS.lock().unwrap().video_refresh = Some(callback);
+}
+
+// Unused, the audio goes through the batch callback
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_audio_sample(_callback: AudioSampleFn) {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_audio_sample_batch(callback: AudioSampleBatchFn) {
+    CALLBACKS.lock().unwrap().audio_sample_batch = Some(callback);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_input_poll(callback: InputPollFn) {
+    CALLBACKS.lock().unwrap().input_poll = Some(callback);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_input_state(callback: InputStateFn) {
+    CALLBACKS.lock().unwrap().input_state = Some(callback);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_init() {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_deinit() {
+    *game() = None;
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut SystemInfo) {
+    unsafe {
+        *info = SystemInfo {
+            library_name: c"CHIP-8".as_ptr(),
+            library_version: c"0.1.0".as_ptr(),
+            valid_extensions: c"ch8|c8".as_ptr(),
+            need_fullpath: false,
+            block_extract: false,
+        };
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut SystemAvInfo) {
+    unsafe {
+        *info = SystemAvInfo {
+            geometry: GameGeometry {
+                base_width: SCREEN_WIDTH as c_uint,
+                base_height: SCREEN_HEIGHT as c_uint,
+                max_width: SCREEN_WIDTH as c_uint,
+                max_height: SCREEN_HEIGHT as c_uint,
+                aspect_ratio: SCREEN_WIDTH as f32 / SCREEN_HEIGHT as f32,
+            },
+            timing: SystemTiming { fps: FPS, sample_rate: SAMPLE_RATE as f64 },
+        };
+    }
+}
+
+// Only the joypad is supported, whatever is plugged in
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_load_game(info: *const GameInfo) -> bool {
+    let Some(info) = (unsafe { info.as_ref() }) else {
+        return false;
+    };
+    if info.data.is_null() || info.size > MAX_ROM_SIZE {
+        return false;
+    }
+    let rom = unsafe { std::slice::from_raw_parts(info.data as *const u8, info.size) }.to_vec();
+
+    let mut format = PIXEL_FORMAT_XRGB8888;
+    if !environment(ENVIRONMENT_SET_PIXEL_FORMAT, &mut format as *mut c_uint as *mut c_void) {
+        return false;
+    }
+
+    let mut descriptors: Vec<InputDescriptor> = BUTTONS
+        .iter()
+        .zip(BUTTON_NAMES.iter())
+        .map(|(&(id, _), name)| InputDescriptor { port: 0, device: DEVICE_JOYPAD, index: 0, id, description: name.as_ptr() })
+        .collect();
+    descriptors.push(InputDescriptor { port: 0, device: 0, index: 0, id: 0, description: std::ptr::null() });
+    environment(ENVIRONMENT_SET_INPUT_DESCRIPTORS, descriptors.as_mut_ptr() as *mut c_void);
+
+    let mut game_state = Game {
+        emu: Emu::new(),
+        rom,
+        ipf: DEFAULT_IPF,
+        pixels: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+        video: vec![BACKGROUND; SCREEN_WIDTH * SCREEN_HEIGHT],
+        synth: BeepSynth::default(),
+        crashed: false,
+    };
+    game_state.apply_options(options());
+    game_state.restart();
+    *game() = Some(game_state);
+    true
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_load_game_special(_type: c_uint, _info: *const GameInfo, _num: usize) -> bool {
+    false
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_unload_game() {
+    *game() = None;
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_reset() {
+    if let Some(game) = game().as_mut() {
+        game.restart();
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_run() {
+    // The frontend is only called with the game unlocked, so its callbacks
+    // can call back into the core, e.g. to save a state
+    let callbacks = callbacks();
+    let mut updated = false;
+    let options = (environment(ENVIRONMENT_GET_VARIABLE_UPDATE, &mut updated as *mut bool as *mut c_void) && updated)
+        .then(options);
+    let buttons = match (callbacks.input_poll, callbacks.input_state) {
+        (Some(poll), Some(state)) => {
+            unsafe { poll() };
+            Some(BUTTONS.map(|(id, key)| (key, unsafe { state(0, DEVICE_JOYPAD, 0, id) } != 0)))
+        },
+        _ => None,
+    };
+
+    let (video, stereo, error) = {
+        let mut game = game();
+        let Some(game) = game.as_mut() else {
+            return;
+        };
+        if let Some(options) = options {
+            game.apply_options(options);
+        }
+        for (key, pressed) in buttons.into_iter().flatten() {
+            game.emu.keypress(key, pressed);
+        }
+
+        let error = game.run_frame();
+        if game.emu.take_dirty().is_some() {
+            game.emu.display().write_bytes(&mut game.pixels);
+            for (color, &lit) in game.video.iter_mut().zip(&game.pixels) {
+                *color = if lit != 0 { FOREGROUND } else { BACKGROUND };
+            }
+        }
+
+        // the beep is mono, the frontend wants interleaved stereo
+        let mut mono = [0; SAMPLES_PER_FRAME];
+        if !game.crashed {
+            game.synth.fill(&game.emu, SAMPLE_RATE, &mut mono);
+        }
+        let stereo: Vec<i16> = mono.iter().flat_map(|&sample| [sample, sample]).collect();
+        (game.video.clone(), stereo, error)
+    };
+
+    if let Some(error) = error {
+        let text = CString::new(format!("CHIP-8 stopped: {error}. Reset or load a state to go on.")).unwrap();
+        let mut message = Message { msg: text.as_ptr(), frames: MESSAGE_FRAMES };
+        environment(ENVIRONMENT_SET_MESSAGE, &mut message as *mut Message as *mut c_void);
+    }
+    if let Some(video_refresh) = callbacks.video_refresh {
+        let pitch = SCREEN_WIDTH * size_of::<u32>();
+        unsafe { video_refresh(video.as_ptr() as *const c_void, SCREEN_WIDTH as c_uint, SCREEN_HEIGHT as c_uint, pitch) };
+    }
+    if let Some(audio_sample_batch) = callbacks.audio_sample_batch {
+        unsafe { audio_sample_batch(stereo.as_ptr(), SAMPLES_PER_FRAME) };
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_serialize_size() -> usize {
+    STATE_SIZE
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    let game = game();
+    let Some(game) = game.as_ref() else {
+        return false;
+    };
+    if data.is_null() || size < STATE_SIZE {
+        return false;
+    }
+    let state = game.emu.save_state();
+    unsafe { std::ptr::copy_nonoverlapping(state.as_ptr(), data as *mut u8, STATE_SIZE) };
+    true
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    let mut game = game();
+    let Some(game) = game.as_mut() else {
+        return false;
+    };
+    if data.is_null() {
+        return false;
+    }
+    let state = unsafe { std::slice::from_raw_parts(data as *const u8, size) };
+    let loaded = game.emu.load_state(state).is_ok();
+    game.crashed &= !loaded;
+    loaded
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const std::ffi::c_char) {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_get_region() -> c_uint {
+    REGION_NTSC
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_get_memory_data(_id: c_uint) -> *mut c_void {
+    std::ptr::null_mut()
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_get_memory_size(_id: c_uint) -> usize {
+    0
+}
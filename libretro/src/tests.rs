@@ -0,0 +1,228 @@
+// Drives the core the way a libretro frontend does, through mock callbacks
+// recording what the core sends back.
+
+use std::ffi::{c_uint, c_void, CStr};
+use std::sync::{Mutex, MutexGuard};
+
+use super::*;
+
+// LD V0, 5; SKP V0; JP 0x202; DRW V1, V1, 5 (the "0" glyph); JP 0x208
+const ROM: [u8; 10] = [0x60, 0x05, 0xE0, 0x9E, 0x12, 0x02, 0xD1, 0x15, 0x12, 0x08];
+const GLYPH_PIXELS: usize = 14;
+
+#[derive(Default)]
+struct Frontend {
+    options: Vec<String>,
+    pixel_format: Option<c_uint>,
+    descriptors: usize,
+    video: Vec<u32>,
+    video_size: (c_uint, c_uint, usize), // width, height, pitch
+    audio: Vec<i16>,
+    audio_frames: usize,
+    polls: usize,
+    held: Vec<c_uint>, // joypad buttons
+    messages: Vec<String>,
+    save_on_refresh: bool, // calls back into the core from video_refresh
+    saved_on_refresh: usize,
+}
+
+static FRONTEND: Mutex<Option<Frontend>> = Mutex::new(None);
+// The core keeps its state in statics, so tests take turns
+static TEST_LOCK: Mutex<()> = Mutex::new(());
+
+fn frontend() -> MutexGuard<'static, Option<Frontend>> {
+    FRONTEND.lock().unwrap()
+}
+
+fn with_frontend<T>(f: impl FnOnce(&mut Frontend) -> T) -> T {
+    f(frontend().as_mut().unwrap())
+}
+
+unsafe extern "C" fn environment_cb(cmd: c_uint, data: *mut c_void) -> bool {
+    with_frontend(|frontend| match cmd {
+        ENVIRONMENT_SET_VARIABLES => {
+            let mut variable = data as *const Variable;
+            while !unsafe { (*variable).key }.is_null() {
+                let key = unsafe { CStr::from_ptr((*variable).key) };
+                frontend.options.push(key.to_string_lossy().into_owned());
+                variable = unsafe { variable.add(1) };
+            }
+            true
+        },
+        ENVIRONMENT_SET_PIXEL_FORMAT => {
+            frontend.pixel_format = Some(unsafe { *(data as *const c_uint) });
+            true
+        },
+        ENVIRONMENT_SET_INPUT_DESCRIPTORS => {
+            let mut descriptor = data as *const InputDescriptor;
+            while !unsafe { (*descriptor).description }.is_null() {
+                frontend.descriptors += 1;
+                descriptor = unsafe { descriptor.add(1) };
+            }
+            true
+        },
+        ENVIRONMENT_SET_MESSAGE => {
+            let message = unsafe { &*(data as *const Message) };
+            frontend.messages.push(unsafe { CStr::from_ptr(message.msg) }.to_string_lossy().into_owned());
+            true
+        },
+        // no options set by the user, so the defaults apply
+        _ => false,
+    })
+}
+
+unsafe extern "C" fn video_refresh_cb(data: *const c_void, width: c_uint, height: c_uint, pitch: usize) {
+    let pixels = unsafe { std::slice::from_raw_parts(data as *const u32, pitch / size_of::<u32>() * height as usize) };
+    let save = with_frontend(|frontend| {
+        frontend.video = pixels.to_vec();
+        frontend.video_size = (width, height, pitch);
+        frontend.save_on_refresh
+    });
+    if save {
+        let mut state = vec![0u8; retro_serialize_size()];
+        let saved = unsafe { retro_serialize(state.as_mut_ptr() as *mut c_void, state.len()) };
+        with_frontend(|frontend| frontend.saved_on_refresh += saved as usize);
+    }
+}
+
+unsafe extern "C" fn audio_sample_batch_cb(data: *const i16, frames: usize) -> usize {
+    let samples = unsafe { std::slice::from_raw_parts(data, frames * 2) };
+    with_frontend(|frontend| {
+        frontend.audio = samples.to_vec();
+        frontend.audio_frames = frames;
+    });
+    frames
+}
+
+unsafe extern "C" fn input_poll_cb() {
+    with_frontend(|frontend| frontend.polls += 1);
+}
+
+unsafe extern "C" fn input_state_cb(port: c_uint, device: c_uint, _index: c_uint, id: c_uint) -> i16 {
+    with_frontend(|frontend| (port == 0 && device == DEVICE_JOYPAD && frontend.held.contains(&id)) as i16)
+}
+
+/// Sets up the callbacks with a fresh frontend and loads `rom`. The guard
+/// keeps other tests out until the end of the test.
+fn start(rom: &[u8]) -> (MutexGuard<'static, ()>, bool) {
+    let guard = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    *frontend() = Some(Frontend::default());
+    retro_set_environment(environment_cb);
+    retro_set_video_refresh(video_refresh_cb);
+    retro_set_audio_sample_batch(audio_sample_batch_cb);
+    retro_set_input_poll(input_poll_cb);
+    retro_set_input_state(input_state_cb);
+    retro_init();
+
+    let info = GameInfo { path: std::ptr::null(), data: rom.as_ptr() as *const c_void, size: rom.len(), meta: std::ptr::null() };
+    let loaded = unsafe { retro_load_game(&info) };
+    (guard, loaded)
+}
+
+fn lit_pixels() -> usize {
+    with_frontend(|frontend| frontend.video.iter().filter(|&&pixel| pixel == FOREGROUND).count())
+}
+
+#[test]
+fn loads_a_game() {
+    let (_guard, loaded) = start(&ROM);
+    assert!(loaded);
+    with_frontend(|frontend| {
+        assert_eq!(frontend.options, ["chip8_ipf", "chip8_timing", "chip8_quirks"]);
+        assert_eq!(frontend.pixel_format, Some(PIXEL_FORMAT_XRGB8888));
+        assert_eq!(frontend.descriptors, BUTTONS.len());
+    });
+
+    // empty and oversized ROMs are refused
+    let empty = GameInfo { path: std::ptr::null(), data: std::ptr::null(), size: 0, meta: std::ptr::null() };
+    assert!(!unsafe { retro_load_game(&empty) });
+    let rom = vec![0; MAX_ROM_SIZE + 1];
+    let oversized = GameInfo { path: std::ptr::null(), data: rom.as_ptr() as *const c_void, size: rom.len(), meta: std::ptr::null() };
+    assert!(!unsafe { retro_load_game(&oversized) });
+    retro_deinit();
+}
+
+#[test]
+fn runs_a_frame_with_the_joypad() {
+    let (_guard, loaded) = start(&ROM);
+    assert!(loaded);
+
+    retro_run();
+    with_frontend(|frontend| {
+        assert_eq!(frontend.polls, 1);
+        assert_eq!(frontend.video_size, (SCREEN_WIDTH as c_uint, SCREEN_HEIGHT as c_uint, SCREEN_WIDTH * 4));
+        assert_eq!(frontend.video.len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+        // a 60th of a second, interleaved stereo, silent
+        assert_eq!(frontend.audio_frames, SAMPLE_RATE as usize / 60);
+        assert_eq!(frontend.audio.len(), frontend.audio_frames * 2);
+        assert!(frontend.audio.iter().all(|&sample| sample == 0));
+    });
+    assert_eq!(lit_pixels(), 0);
+
+    // A is key 5, which the program waits for
+    with_frontend(|frontend| frontend.held.push(DEVICE_ID_JOYPAD_A));
+    retro_run();
+    assert_eq!(lit_pixels(), GLYPH_PIXELS);
+    retro_deinit();
+}
+
+#[test]
+fn save_states_round_trip() {
+    let (_guard, loaded) = start(&ROM);
+    assert!(loaded);
+    retro_run();
+
+    let mut state = vec![0u8; retro_serialize_size()];
+    assert!(unsafe { retro_serialize(state.as_mut_ptr() as *mut c_void, state.len()) });
+    assert!(!unsafe { retro_serialize(state.as_mut_ptr() as *mut c_void, state.len() - 1) });
+
+    // the key press draws the glyph, loading the state takes it back
+    with_frontend(|frontend| frontend.held.push(DEVICE_ID_JOYPAD_A));
+    retro_run();
+    assert_eq!(lit_pixels(), GLYPH_PIXELS);
+    assert!(unsafe { retro_unserialize(state.as_ptr() as *const c_void, state.len()) });
+    let mut restored = vec![0u8; retro_serialize_size()];
+    assert!(unsafe { retro_serialize(restored.as_mut_ptr() as *mut c_void, restored.len()) });
+    assert_eq!(restored, state);
+
+    with_frontend(|frontend| frontend.held.clear());
+    retro_run();
+    assert_eq!(lit_pixels(), 0);
+
+    assert!(!unsafe { retro_unserialize(state.as_ptr() as *const c_void, state.len() - 1) });
+    retro_deinit();
+}
+
+#[test]
+fn bad_opcodes_stop_the_game() {
+    // LD V0, 1; 00FF (SUPER-CHIP high resolution)
+    let (_guard, loaded) = start(&[0x60, 0x01, 0x00, 0xFF]);
+    assert!(loaded);
+
+    // reported once, and the frontend still gets its video and audio
+    for _ in 0..3 {
+        retro_run();
+    }
+    with_frontend(|frontend| {
+        assert_eq!(frontend.messages.len(), 1);
+        assert!(frontend.messages[0].contains("unknown opcode 00FF at 0x202"), "{}", frontend.messages[0]);
+        assert_eq!(frontend.video.len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+        assert_eq!(frontend.audio_frames, SAMPLE_RATE as usize / 60);
+    });
+
+    // a reset starts the game again, up to the same opcode
+    retro_reset();
+    retro_run();
+    with_frontend(|frontend| assert_eq!(frontend.messages.len(), 2));
+    retro_deinit();
+}
+
+#[test]
+fn callbacks_can_call_the_core() {
+    let (_guard, loaded) = start(&ROM);
+    assert!(loaded);
+    with_frontend(|frontend| frontend.save_on_refresh = true);
+    retro_run();
+    with_frontend(|frontend| assert_eq!(frontend.saved_on_refresh, 1));
+    retro_deinit();
+}
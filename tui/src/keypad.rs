@@ -0,0 +1,65 @@
+use std::time::{Duration, Instant};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+pub const NUM_KEYS: usize = 16;
+
+// The same 4x4 block as the GUI's default layout, by character since
+// terminals don't report physical keys
+//
+//   1 2 3 C        1 2 3 4
+//   4 5 6 D   <=   Q W E R
+//   7 8 9 E        A S D F
+//   A 0 B F        Z X C V
+const KEYS: [char; NUM_KEYS] = [
+    'x', '1', '2', '3', 'q', 'w', 'e', 'a', 's', 'd', 'z', 'c', '4', 'r', 'f', 'v',
+];
+
+/// CHIP-8 keypad state from terminal key events. Most terminals only report
+/// presses, repeated while a key is held, so unless the terminal reports
+/// releases a key counts as released once no press was seen for `hold`.
+pub struct Keypad {
+    pressed: [Option<Instant>; NUM_KEYS], // time of the last press
+    hold: Duration,
+    releases: bool, // the terminal reports key releases
+}
+
+impl Keypad {
+    pub fn new(hold: Duration, releases: bool) -> Self {
+        Self { pressed: [None; NUM_KEYS], hold, releases }
+    }
+
+    /// Handles a key event. Returns false if it isn't a keypad key.
+    pub fn handle(&mut self, event: &KeyEvent, now: Instant) -> bool {
+        // leave Ctrl-C and friends to the frontend
+        let KeyCode::Char(c) = event.code else {
+            return false;
+        };
+        if event.modifiers.contains(KeyModifiers::CONTROL) {
+            return false;
+        }
+        let Some(key) = KEYS.iter().position(|&k| k == c.to_ascii_lowercase()) else {
+            return false;
+        };
+
+        match event.kind {
+            KeyEventKind::Release => self.pressed[key] = None,
+            // with real releases, repeats don't change anything
+            KeyEventKind::Repeat if self.releases => {},
+            _ => self.pressed[key] = Some(now),
+        }
+        true
+    }
+
+    pub fn is_down(&self, key: usize, now: Instant) -> bool {
+        match self.pressed[key] {
+            Some(_) if self.releases => true,
+            Some(pressed) => now - pressed < self.hold,
+            None => false,
+        }
+    }
+
+    pub fn release_all(&mut self) {
+        self.pressed = [None; NUM_KEYS];
+    }
+}
@@ -0,0 +1,216 @@
+// Terminal frontend: draws the screen with half-block characters, two
+// CHIP-8 pixels per character cell, and rings the terminal bell for the
+// sound timer. Works over SSH, no display server needed.
+
+use std::io::{self, Stdout, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::Print;
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+
+use core::*;
+use core::cli::{exit_with, flag_value, quirks_or_exit};
+
+mod keypad;
+
+use keypad::{Keypad, NUM_KEYS};
+
+const TIMER_HZ: u32 = 60;
+const DEFAULT_IPF: usize = 10;
+const MAX_IPF: usize = 1000;
+const DEFAULT_KEY_HOLD_MS: u64 = 200;
+// Terminal cells needed: the screen plus a status line
+const COLUMNS: u16 = SCREEN_WIDTH as u16;
+const ROWS: u16 = SCREEN_HEIGHT as u16 / 2 + 1;
+const BELL: &str = "\x07";
+
+struct Args {
+    rom_path: PathBuf,
+    ipf: usize,
+    quirks: Quirks,
+    timing: Timing,
+    key_hold: Duration,
+}
+
+fn parse_args() -> Args {
+    let mut rom_path = None;
+    let mut args = Args {
+        rom_path: PathBuf::new(),
+        ipf: DEFAULT_IPF,
+        quirks: Quirks::default(),
+        timing: Timing::Instructions,
+        key_hold: Duration::from_millis(DEFAULT_KEY_HOLD_MS),
+    };
+    let mut argv = std::env::args().skip(1);
+
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "--ipf" => args.ipf = flag_value(&mut argv, &arg, "a number of instructions per frame"),
+            "--quirks" => args.quirks = quirks_or_exit(&flag_value::<String>(&mut argv, &arg, "a quirk profile")),
+            "--vip-timing" => args.timing = Timing::CosmacVip,
+            "--key-hold" => args.key_hold = Duration::from_millis(flag_value(&mut argv, &arg, "a time in milliseconds")),
+            _ => rom_path = Some(PathBuf::from(arg)),
+        }
+    }
+
+    args.rom_path = rom_path.unwrap_or_else(|| {
+        exit_with("usage: tui [--ipf N] [--quirks PROFILE] [--vip-timing] [--key-hold MS] ROM")
+    });
+    if args.ipf == 0 {
+        exit_with("ipf must be at least 1");
+    }
+    args
+}
+
+/// Raw mode and the alternate screen, undone on drop so the shell is usable
+/// again even if the emulator panics.
+struct RawTerminal {
+    out: Stdout,
+    releases: bool, // key release events were enabled
+}
+
+impl RawTerminal {
+    fn new() -> io::Result<Self> {
+        let mut out = io::stdout();
+        terminal::enable_raw_mode()?;
+        execute!(out, EnterAlternateScreen, Hide, Clear(ClearType::All))?;
+
+        // terminals implementing the kitty keyboard protocol can report
+        // releases, which makes holding keys exact
+        let releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if releases {
+            let flags = KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES | KeyboardEnhancementFlags::REPORT_EVENT_TYPES;
+            execute!(out, PushKeyboardEnhancementFlags(flags))?;
+        }
+        Ok(Self { out, releases })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        if self.releases {
+            let _ = execute!(self.out, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(self.out, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn draw(out: &mut impl Write, chip8: &HeadlessEmu, status: &str) -> io::Result<()> {
+    let (columns, rows) = terminal::size()?;
+    if columns < COLUMNS || rows < ROWS {
+        queue!(out, Clear(ClearType::All), MoveTo(0, 0), Print(format!("Terminal too small, need {COLUMNS}x{ROWS}")))?;
+        return out.flush();
+    }
+
+    // each character cell shows two rows of pixels
+    let screen = chip8.get_screen();
+    for (row, pair) in screen.chunks(2).enumerate() {
+        let line: String = (0..SCREEN_WIDTH)
+            .map(|x| {
+                let bit = 1 << (SCREEN_WIDTH - 1 - x);
+                match (pair[0] & bit != 0, pair[1] & bit != 0) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                }
+            })
+            .collect();
+        queue!(out, MoveTo(0, row as u16), Print(line))?;
+    }
+    queue!(out, MoveTo(0, ROWS - 1), Clear(ClearType::CurrentLine), Print(status))?;
+    out.flush()
+}
+
+fn run(terminal: &mut RawTerminal, chip8: &mut HeadlessEmu, args: &Args, title: &str) -> io::Result<()> {
+    let frame_duration = Duration::from_secs(1) / TIMER_HZ;
+    let mut next_frame = Instant::now();
+    let mut keypad = Keypad::new(args.key_hold, terminal.releases);
+    let mut ipf = args.ipf;
+    let mut paused = false;
+    let mut redraw = true;
+    let mut was_beeping = false;
+
+    loop {
+        // handle input until the next frame is due
+        while event::poll(next_frame.saturating_duration_since(Instant::now()))? {
+            let now = Instant::now();
+            match event::read()? {
+                Event::Key(key) => {
+                    if keypad.handle(&key, now) || key.kind == KeyEventKind::Release {
+                        continue;
+                    }
+                    match key.code {
+                        KeyCode::Esc => return Ok(()),
+                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+                        KeyCode::Char('p') | KeyCode::Char(' ') => {
+                            paused = !paused;
+                            keypad.release_all();
+                        },
+                        KeyCode::Char('-') => ipf = ipf.saturating_sub(1).max(1),
+                        KeyCode::Char('=') => ipf = (ipf + 1).min(MAX_IPF),
+                        _ => {},
+                    }
+                    redraw = true;
+                },
+                Event::Resize(..) => {
+                    queue!(terminal.out, Clear(ClearType::All))?;
+                    redraw = true;
+                },
+                _ => {},
+            }
+        }
+
+        let now = Instant::now();
+        if !paused {
+            for key in 0..NUM_KEYS {
+                chip8.keypress(key, keypad.is_down(key, now));
+            }
+            chip8.run_frame(ipf);
+        }
+
+        // ring once per beep; the terminal decides what a bell sounds like
+        if chip8.is_beeping() && !was_beeping {
+            queue!(terminal.out, Print(BELL))?;
+        }
+        was_beeping = chip8.is_beeping();
+
+        if chip8.take_dirty().is_some() || redraw {
+            let state = if paused { "PAUSED" } else { "" };
+            let status = format!("{title}  IPF {ipf}  {state:6}  Esc quit, P pause, -/= IPF");
+            draw(&mut terminal.out, chip8, &status)?;
+            redraw = false;
+        }
+
+        // don't try to catch up after a stall (e.g. a suspended SSH session)
+        next_frame = (next_frame + frame_duration).max(now);
+    }
+}
+
+fn main() {
+    let args = parse_args();
+    let rom = std::fs::read(&args.rom_path)
+        .unwrap_or_else(|e| exit_with(format!("Error reading {}: {e}", args.rom_path.display())));
+    let title = args.rom_path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+
+    // the terminal bell replaces the beep, which would play on the remote
+    // machine over SSH
+    let mut chip8 = HeadlessEmu::headless();
+    chip8.set_quirks(args.quirks);
+    chip8.set_timing(args.timing);
+    chip8.load_rom(&rom);
+
+    let result = RawTerminal::new().and_then(|mut terminal| run(&mut terminal, &mut chip8, &args, &title));
+    // the terminal is restored by now, errors can be printed normally
+    if let Err(e) = result {
+        exit_with(format!("Terminal error: {e}"));
+    }
+}
@@ -0,0 +1,100 @@
+// WebAssembly wrapper around the core for the browser page in www/. The page
+// owns the timing (requestAnimationFrame), the keyboard and the beep; this
+// only forwards calls and exposes the screen as bytes in wasm memory.
+
+use wasm_bindgen::prelude::*;
+
+use chip8::{Emu, Quirks, MAX_ROM_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+#[wasm_bindgen]
+pub struct Chip8 {
+    emu: Emu,
+    pixels: Vec<u8>, // one byte per pixel, 0 or 1, row by row
+}
+
+#[wasm_bindgen]
+impl Chip8 {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self { emu: Emu::new(), pixels: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT] }
+    }
+
+    /// Resets the machine and loads `rom` at 0x200.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), JsError> {
+        if rom.len() > MAX_ROM_SIZE {
+            return Err(JsError::new(&format!("ROM is {} bytes, at most {MAX_ROM_SIZE} fit in memory", rom.len())));
+        }
+        self.emu.reset();
+        self.emu.load_rom(rom);
+        Ok(())
+    }
+
+    /// Runs one instruction. Fails, leaving the machine as it is, on one it
+    /// can't run (an unknown opcode, a return with an empty stack...); load
+    /// a ROM to go on.
+    pub fn tick(&mut self) -> Result<(), JsError> {
+        self.emu.try_tick().map_err(|fault| JsError::new(&fault.to_string()))
+    }
+
+    /// Counts the delay and sound timers down, call at 60 Hz.
+    pub fn tick_timers(&mut self) {
+        self.emu.tick_timers();
+    }
+
+    /// One 60 Hz frame: `ipf` instructions, then the timers. Fails like
+    /// `tick`.
+    pub fn run_frame(&mut self, ipf: usize) -> Result<(), JsError> {
+        self.emu.try_run_frame(ipf).map_err(|fault| JsError::new(&fault.to_string()))
+    }
+
+    pub fn keypress(&mut self, key: usize, pressed: bool) {
+        self.emu.keypress(key, pressed);
+    }
+
+    /// Selects a quirk profile by name, returns false if it's unknown.
+    pub fn set_quirks(&mut self, name: &str) -> bool {
+        match Quirks::from_name(name) {
+            Some(quirks) => {
+                self.emu.set_quirks(quirks);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Seeds CXNN. Without it every page load plays out the same way.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.emu.set_seed(seed);
+    }
+
+    pub fn is_beeping(&self) -> bool {
+        self.emu.is_beeping()
+    }
+
+    /// True if the screen changed since the last call.
+    pub fn take_dirty(&mut self) -> bool {
+        self.emu.take_dirty().is_some()
+    }
+
+    /// Pointer to the screen in wasm memory, `width() * height()` bytes of
+    /// 0 or 1, row by row. Valid until the next call on this object; memory
+    /// growth detaches views on it, so make a new one each time.
+    pub fn framebuffer(&mut self) -> *const u8 {
+        self.emu.display().write_bytes(&mut self.pixels);
+        self.pixels.as_ptr()
+    }
+
+    pub fn width() -> usize {
+        SCREEN_WIDTH
+    }
+
+    pub fn height() -> usize {
+        SCREEN_HEIGHT
+    }
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,103 @@
+// Runs in Node.js, see .cargo/config.toml
+#![cfg(target_arch = "wasm32")]
+
+use wasm_bindgen_test::*;
+
+use web::Chip8;
+
+// Pixels set in the framebuffer, read through its pointer like the page does
+fn lit_pixels(chip8: &mut Chip8) -> Vec<(usize, usize)> {
+    let ptr = chip8.framebuffer();
+    let pixels = unsafe { std::slice::from_raw_parts(ptr, Chip8::width() * Chip8::height()) };
+    (0..pixels.len())
+        .filter(|&i| pixels[i] != 0)
+        .map(|i| (i % Chip8::width(), i / Chip8::width()))
+        .collect()
+}
+
+#[wasm_bindgen_test]
+fn draws_a_sprite() {
+    let mut chip8 = Chip8::new();
+    // LD V0, 60; LD I, 0x208; DRW V0, V0, 1; sprite 0b1100_0000
+    chip8.load_rom(&[0x60, 0x3C, 0xA2, 0x08, 0xD0, 0x01, 0x00, 0x00, 0xC0]).unwrap();
+    // a fresh screen needs drawing once
+    assert!(chip8.take_dirty());
+    assert!(!chip8.take_dirty());
+    for _ in 0..3 {
+        chip8.tick().unwrap();
+    }
+    assert!(chip8.take_dirty());
+    assert_eq!(lit_pixels(&mut chip8), [(60, 60 % 32), (61, 60 % 32)]);
+
+    // reloading clears the screen
+    chip8.load_rom(&[]).unwrap();
+    assert!(lit_pixels(&mut chip8).is_empty());
+}
+
+#[wasm_bindgen_test]
+fn timers_run_the_beep() {
+    let mut chip8 = Chip8::new();
+    // LD V0, 2; LD ST, V0
+    chip8.load_rom(&[0x60, 0x02, 0xF0, 0x18]).unwrap();
+    chip8.run_frame(2).unwrap();
+    assert!(chip8.is_beeping());
+    chip8.tick_timers();
+    assert!(chip8.is_beeping());
+    chip8.tick_timers();
+    assert!(!chip8.is_beeping());
+}
+
+#[wasm_bindgen_test]
+fn keypress_reaches_the_program() {
+    let mut chip8 = Chip8::new();
+    // LD V0, 0xA; SKP V0; JP 0x202; DRW V1, V1, 5 (the "0" glyph); JP 0x208
+    chip8.load_rom(&[0x60, 0x0A, 0xE0, 0x9E, 0x12, 0x02, 0xD1, 0x15, 0x12, 0x08]).unwrap();
+    chip8.take_dirty();
+    chip8.run_frame(10).unwrap();
+    assert!(!chip8.take_dirty());
+
+    chip8.keypress(0xA, true);
+    chip8.run_frame(10).unwrap();
+    assert!(chip8.take_dirty());
+    assert_eq!(lit_pixels(&mut chip8).len(), 14); // the "0" glyph
+}
+
+#[wasm_bindgen_test]
+fn seeded_runs_match() {
+    // RND V0, 0xFF; LD F, V0; DRW V1, V1, 5; JP 0x200
+    let rom = [0xC0, 0xFF, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x00];
+    let run = |seed| {
+        let mut chip8 = Chip8::new();
+        chip8.set_seed(seed);
+        chip8.load_rom(&rom).unwrap();
+        for _ in 0..20 {
+            chip8.run_frame(16).unwrap();
+        }
+        lit_pixels(&mut chip8)
+    };
+    assert_eq!(run(7), run(7));
+    assert_ne!(run(7), run(8));
+}
+
+#[wasm_bindgen_test]
+fn set_quirks_takes_profile_names() {
+    assert!(Chip8::new().set_quirks("schip"));
+    assert!(!Chip8::new().set_quirks("nes"));
+}
+
+#[wasm_bindgen_test]
+fn rejects_oversized_roms() {
+    assert!(Chip8::new().load_rom(&[0; 4096]).is_err());
+}
+
+#[wasm_bindgen_test]
+fn unsupported_opcodes_stop_the_machine() {
+    let mut chip8 = Chip8::new();
+    // LD V0, 1; 00FF (SUPER-CHIP high resolution)
+    chip8.load_rom(&[0x60, 0x01, 0x00, 0xFF]).unwrap();
+    assert!(chip8.run_frame(10).is_err());
+    // still usable: the same error again, then a new ROM runs
+    assert!(chip8.tick().is_err());
+    chip8.load_rom(&[0x60, 0x01]).unwrap();
+    assert!(chip8.tick().is_ok());
+}
@@ -0,0 +1,109 @@
+// Build the module with (from web/):
+//   cargo build --release --target wasm32-unknown-unknown
+//   wasm-bindgen --target web --out-dir www/pkg target/wasm32-unknown-unknown/release/web.wasm
+// and serve www/ over HTTP, e.g. `python3 -m http.server -d www`.
+import init, { Chip8 } from "./pkg/web.js";
+
+const FOREGROUND = [0xFF, 0xFF, 0xFF];
+const BACKGROUND = [0x00, 0x00, 0x00];
+const BEEP_PITCH = 440; // Hz
+const BEEP_VOLUME = 0.2;
+
+// Same layout as the desktop frontend, by physical key
+const KEYS = {
+    Digit1: 0x1, Digit2: 0x2, Digit3: 0x3, Digit4: 0xC,
+    KeyQ: 0x4, KeyW: 0x5, KeyE: 0x6, KeyR: 0xD,
+    KeyA: 0x7, KeyS: 0x8, KeyD: 0x9, KeyF: 0xE,
+    KeyZ: 0xA, KeyX: 0x0, KeyC: 0xB, KeyV: 0xF,
+};
+
+const wasm = await init();
+const chip8 = new Chip8();
+chip8.set_seed(BigInt(Math.floor(Math.random() * 2 ** 53)));
+
+const canvas = document.getElementById("screen");
+const context = canvas.getContext("2d");
+const image = context.createImageData(Chip8.width(), Chip8.height());
+const ipfInput = document.getElementById("ipf");
+let running = false;
+let paused = false;
+
+function draw() {
+    // a new view every time, growing the memory detaches the old one
+    const pixels = new Uint8Array(wasm.memory.buffer, chip8.framebuffer(), Chip8.width() * Chip8.height());
+    pixels.forEach((on, i) => {
+        image.data.set(on ? FOREGROUND : BACKGROUND, i * 4);
+        image.data[i * 4 + 3] = 0xFF;
+    });
+    context.putImageData(image, 0, 0);
+}
+
+// The oscillator is created on the first key press, browsers only allow
+// audio after a user gesture
+let audio = null;
+let gain = null;
+function beep(on) {
+    if (gain) {
+        gain.gain.value = on ? BEEP_VOLUME : 0;
+    }
+}
+
+document.getElementById("rom").addEventListener("change", async (event) => {
+    const file = event.target.files[0];
+    if (!file) {
+        return;
+    }
+    try {
+        chip8.load_rom(new Uint8Array(await file.arrayBuffer()));
+        running = true;
+        draw();
+    } catch (error) {
+        alert(error.message);
+    }
+});
+
+function onKey(event, pressed) {
+    if (!audio) {
+        audio = new AudioContext();
+        const oscillator = new OscillatorNode(audio, { frequency: BEEP_PITCH, type: "square" });
+        gain = new GainNode(audio, { gain: 0 });
+        oscillator.connect(gain).connect(audio.destination);
+        oscillator.start();
+    }
+    if (event.code === "KeyP" && pressed) {
+        paused = !paused;
+    } else if (event.code in KEYS) {
+        chip8.keypress(KEYS[event.code], pressed);
+    } else {
+        return;
+    }
+    event.preventDefault();
+}
+document.addEventListener("keydown", (event) => onKey(event, true));
+document.addEventListener("keyup", (event) => onKey(event, false));
+
+// CHIP-8 frames are 1/60 s whatever the display's refresh rate
+const FRAME_MS = 1000 / 60;
+let last = performance.now();
+let behind = 0;
+function frame(now) {
+    behind = Math.min(behind + now - last, 10 * FRAME_MS); // no catching up after a hidden tab
+    last = now;
+    for (; behind >= FRAME_MS; behind -= FRAME_MS) {
+        if (running && !paused) {
+            try {
+                chip8.run_frame(Math.max(1, ipfInput.valueAsNumber || 1));
+            } catch (error) {
+                // the machine stays stopped until another ROM is loaded
+                running = false;
+                alert(`The program stopped: ${error.message}`);
+            }
+        }
+    }
+    beep(running && !paused && chip8.is_beeping());
+    if (chip8.take_dirty()) {
+        draw();
+    }
+    requestAnimationFrame(frame);
+}
+requestAnimationFrame(frame);